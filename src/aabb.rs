@@ -0,0 +1,97 @@
+use crate::vector2d::Vector2D;
+
+/// Axis aligned bounding box in world space, used by the broadphase
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector2D,
+    pub max: Vector2D,
+}
+
+impl Aabb {
+    pub fn new(min: Vector2D, max: Vector2D) -> Self {
+        Self { min, max }
+    }
+
+    /// Smallest box containing all the points, `None` for an empty slice
+    pub fn from_points(points: &[Vector2D]) -> Option<Self> {
+        let (first, rest) = points.split_first()?;
+        let mut aabb = Self::new(*first, *first);
+        for point in rest {
+            aabb.min = Vector2D::new(aabb.min.x.min(point.x), aabb.min.y.min(point.y));
+            aabb.max = Vector2D::new(aabb.max.x.max(point.x), aabb.max.y.max(point.y));
+        }
+        Some(aabb)
+    }
+
+    /// Touching boxes count as overlapping
+    pub fn overlaps(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && other.min.x <= self.max.x
+            && self.min.y <= other.max.y && other.min.y <= self.max.y
+    }
+
+    /// True if `other` lies completely inside this box
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.x <= other.min.x && self.min.y <= other.min.y
+            && other.max.x <= self.max.x && other.max.y <= self.max.y
+    }
+
+    pub fn contains_point(&self, point: Vector2D) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x
+            && point.y >= self.min.y && point.y <= self.max.y
+    }
+
+    /// Distance along the ray at which it enters the box (0 if it starts inside),
+    /// `None` if it misses the box within `max_distance`. `direction` must be unit length.
+    pub fn raycast(&self, origin: Vector2D, direction: Vector2D, max_distance: f32) -> Option<f32> {
+        let mut enter = 0.0_f32;
+        let mut exit = max_distance;
+        // Slab test, clip the ray against the x and then the y extent
+        for (start, step, min, max) in [
+            (origin.x, direction.x, self.min.x, self.max.x),
+            (origin.y, direction.y, self.min.y, self.max.y),
+        ] {
+            if step == 0.0 {
+                if start < min || start > max {
+                    return None;
+                }
+                continue;
+            }
+            let near = (min - start) / step;
+            let far = (max - start) / step;
+            enter = enter.max(near.min(far));
+            exit = exit.min(near.max(far));
+            if enter > exit {
+                return None;
+            }
+        }
+        Some(enter)
+    }
+
+    /// Box grown by `margin` on every side
+    pub fn expanded(&self, margin: f32) -> Self {
+        let extent = Vector2D::new(margin, margin);
+        Self::new(self.min - extent, self.max + extent)
+    }
+
+    /// Smallest box containing both boxes
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self::new(
+            Vector2D::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            Vector2D::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        )
+    }
+
+    /// Cost measure used to build the dynamic tree
+    pub fn perimeter(&self) -> f32 {
+        let size = self.size();
+        2.0 * (size.x + size.y)
+    }
+
+    pub fn center(&self) -> Vector2D {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vector2D {
+        self.max - self.min
+    }
+}
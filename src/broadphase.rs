@@ -0,0 +1,327 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::aabb::Aabb;
+use crate::vector2d::Vector2D;
+
+/// Finds the body pairs whose bounding boxes overlap, so the narrowphase
+/// (`detect_collision`) only runs on pairs that can actually touch.
+/// Bodies are identified by the slot index of their `BodyHandle`.
+pub trait BroadPhase {
+    /// Inserts the proxy of body `index`, or moves it if it already exists
+    fn update(&mut self, index: usize, aabb: Aabb);
+
+    fn remove(&mut self, index: usize);
+
+    fn clear(&mut self);
+
+    /// Overlapping pairs as `(lower index, higher index)`, sorted
+    fn collect_pairs(&mut self) -> Vec<(usize, usize)>;
+
+    /// Overlapping pairs with at least one body `is_active` holds for, like `collect_pairs`.
+    /// Lets sleeping bodies be skipped, by default filters the result of `collect_pairs`.
+    fn collect_active_pairs(&mut self, is_active: &dyn Fn(usize) -> bool) -> Vec<(usize, usize)> {
+        self.collect_pairs()
+            .into_iter()
+            .filter(|&(a, b)| is_active(a) || is_active(b))
+            .collect()
+    }
+
+    /// Indices of the bodies whose bounding box overlaps `aabb`, sorted
+    fn query_aabb(&self, aabb: &Aabb) -> Vec<usize>;
+
+    /// Indices of the bodies whose bounding box the ray hits within `max_distance`, sorted.
+    /// `direction` must be unit length. By default queries the box around the ray,
+    /// which wastes time on long diagonal rays.
+    fn query_ray(&self, origin: Vector2D, direction: Vector2D, max_distance: f32) -> Vec<usize> {
+        let end = origin + direction * max_distance;
+        let bounds = Aabb::from_points(&[origin, end]).expect("two points");
+        self.query_aabb(&bounds)
+    }
+}
+
+/// Range of grid cells covered by a box: (min x, min y, max x, max y)
+type CellRange = (i32, i32, i32, i32);
+
+/// Uniform grid hashed by cell coordinates.
+/// Works best when bodies are about the size of a cell, a body spanning many cells
+/// is stored in every one of them.
+pub struct SpatialHash {
+    cell_size: f32,
+    proxies: Vec<Option<(Aabb, CellRange)>>,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(64.0)
+    }
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            proxies: Vec::new(),
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell_range(&self, aabb: &Aabb) -> CellRange {
+        (
+            (aabb.min.x / self.cell_size).floor() as i32,
+            (aabb.min.y / self.cell_size).floor() as i32,
+            (aabb.max.x / self.cell_size).floor() as i32,
+            (aabb.max.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn cells_in(range: CellRange) -> impl Iterator<Item = (i32, i32)> {
+        let (min_x, min_y, max_x, max_y) = range;
+        (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+    }
+
+    fn unlink(&mut self, index: usize, range: CellRange) {
+        for cell in Self::cells_in(range) {
+            if let Some(members) = self.cells.get_mut(&cell) {
+                members.retain(|&member| member != index);
+                if members.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        }
+    }
+
+    fn aabb_of(&self, index: usize) -> Option<&Aabb> {
+        self.proxies.get(index)?.as_ref().map(|(aabb, _)| aabb)
+    }
+
+    /// Proxies stored in the cells of `range`, with repeats
+    fn members_in(&self, range: CellRange) -> Vec<usize> {
+        let (min_x, min_y, max_x, max_y) = range;
+        let count = (max_x as f64 - min_x as f64 + 1.0) * (max_y as f64 - min_y as f64 + 1.0);
+        if count <= self.cells.len() as f64 {
+            Self::cells_in(range)
+                .filter_map(|cell| self.cells.get(&cell))
+                .flatten()
+                .copied()
+                .collect()
+        } else {
+            // A range over more cells than are occupied, go through the occupied ones instead
+            self.cells.iter()
+                .filter(|((x, y), _)| (min_x..=max_x).contains(x) && (min_y..=max_y).contains(y))
+                .flat_map(|(_, members)| members)
+                .copied()
+                .collect()
+        }
+    }
+
+    /// Cells the ray passes through within `max_distance`, in order (grid traversal by Amanatides and Woo)
+    fn ray_cells(&self, origin: Vector2D, direction: Vector2D, max_distance: f32) -> Vec<(i32, i32)> {
+        let mut cell = ((origin.x / self.cell_size).floor() as i32, (origin.y / self.cell_size).floor() as i32);
+        // Per axis: which way the cell index steps, the distance to the first cell border, between borders
+        let axis = |start: f32, step: f32, index: i32| {
+            if step == 0.0 {
+                return (0, f32::INFINITY, f32::INFINITY);
+            }
+            let border = if step > 0.0 { index + 1 } else { index } as f32 * self.cell_size;
+            (step.signum() as i32, (border - start) / step, self.cell_size / step.abs())
+        };
+        let (step_x, mut next_x, delta_x) = axis(origin.x, direction.x, cell.0);
+        let (step_y, mut next_y, delta_y) = axis(origin.y, direction.y, cell.1);
+
+        let mut cells = vec![cell];
+        loop {
+            if next_x < next_y {
+                if next_x > max_distance {
+                    break;
+                }
+                cell.0 += step_x;
+                next_x += delta_x;
+            } else {
+                if next_y > max_distance {
+                    break;
+                }
+                cell.1 += step_y;
+                next_y += delta_y;
+            }
+            cells.push(cell);
+        }
+        cells
+    }
+}
+
+impl BroadPhase for SpatialHash {
+    fn update(&mut self, index: usize, aabb: Aabb) {
+        if index >= self.proxies.len() {
+            self.proxies.resize(index + 1, None);
+        }
+
+        let range = self.cell_range(&aabb);
+        match self.proxies[index] {
+            // Still in the same cells, only the box changed
+            Some((_, old_range)) if old_range == range => {}
+            Some((_, old_range)) => {
+                self.unlink(index, old_range);
+                for cell in Self::cells_in(range) {
+                    self.cells.entry(cell).or_default().push(index);
+                }
+            }
+            None => {
+                for cell in Self::cells_in(range) {
+                    self.cells.entry(cell).or_default().push(index);
+                }
+            }
+        }
+        self.proxies[index] = Some((aabb, range));
+    }
+
+    fn remove(&mut self, index: usize) {
+        if let Some((_, range)) = self.proxies.get_mut(index).and_then(Option::take) {
+            self.unlink(index, range);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.proxies.clear();
+        self.cells.clear();
+    }
+
+    fn collect_pairs(&mut self) -> Vec<(usize, usize)> {
+        // Bodies sharing several cells would be reported once per shared cell
+        let mut pairs = HashSet::new();
+        for members in self.cells.values() {
+            for (i, &a) in members.iter().enumerate() {
+                for &b in &members[i + 1..] {
+                    let pair = (a.min(b), a.max(b));
+                    if pairs.contains(&pair) {
+                        continue;
+                    }
+                    if let (Some(aabb_a), Some(aabb_b)) = (self.aabb_of(a), self.aabb_of(b))
+                        && aabb_a.overlaps(aabb_b)
+                    {
+                        pairs.insert(pair);
+                    }
+                }
+            }
+        }
+
+        let mut pairs: Vec<(usize, usize)> = pairs.into_iter().collect();
+        pairs.sort_unstable();
+        pairs
+    }
+
+    fn query_aabb(&self, aabb: &Aabb) -> Vec<usize> {
+        let mut result: Vec<usize> = self.members_in(self.cell_range(aabb))
+            .into_iter()
+            .filter(|&index| self.aabb_of(index).is_some_and(|other| other.overlaps(aabb)))
+            .collect();
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+
+    // Walks the cells along the ray, the box around a long ray covers far too many
+    fn query_ray(&self, origin: Vector2D, direction: Vector2D, max_distance: f32) -> Vec<usize> {
+        let hits = |index: &usize| self.aabb_of(*index)
+            .is_some_and(|aabb| aabb.raycast(origin, direction, max_distance).is_some());
+        // About how many cells the ray crosses, past the number of proxies testing each one is cheaper
+        let crossed = (direction.x.abs() + direction.y.abs()) * max_distance / self.cell_size;
+        let mut result: Vec<usize> = if crossed < self.proxies.len() as f32 {
+            self.ray_cells(origin, direction, max_distance).into_iter()
+                .filter_map(|cell| self.cells.get(&cell))
+                .flatten()
+                .copied()
+                .filter(hits)
+                .collect()
+        } else {
+            (0..self.proxies.len()).filter(hits).collect()
+        };
+        result.sort_unstable();
+        result.dedup();
+        result
+    }
+}
+
+/// Sort and sweep along the x axis.
+/// The order is kept between steps, so re-sorting the nearly sorted list is cheap.
+#[derive(Default)]
+pub struct SweepAndPrune {
+    aabbs: Vec<Option<Aabb>>,
+    // Live proxies sorted by `min.x`
+    order: Vec<usize>,
+}
+
+impl SweepAndPrune {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn min_x(&self, index: usize) -> f32 {
+        self.aabbs[index].map_or(f32::MAX, |aabb| aabb.min.x)
+    }
+
+    /// Insertion sort, close to linear when bodies moved only a little since the last sort
+    fn sort(&mut self) {
+        for i in 1..self.order.len() {
+            let mut j = i;
+            while j > 0 && self.min_x(self.order[j - 1]) > self.min_x(self.order[j]) {
+                self.order.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+    }
+}
+
+impl BroadPhase for SweepAndPrune {
+    fn update(&mut self, index: usize, aabb: Aabb) {
+        if index >= self.aabbs.len() {
+            self.aabbs.resize(index + 1, None);
+        }
+        if self.aabbs[index].is_none() {
+            self.order.push(index);
+        }
+        self.aabbs[index] = Some(aabb);
+    }
+
+    fn remove(&mut self, index: usize) {
+        if self.aabbs.get_mut(index).and_then(Option::take).is_some() {
+            self.order.retain(|&other| other != index);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.aabbs.clear();
+        self.order.clear();
+    }
+
+    fn collect_pairs(&mut self) -> Vec<(usize, usize)> {
+        self.sort();
+
+        let mut pairs = Vec::new();
+        for (i, &a) in self.order.iter().enumerate() {
+            let Some(aabb_a) = self.aabbs[a] else { continue };
+            for &b in &self.order[i + 1..] {
+                let Some(aabb_b) = self.aabbs[b] else { continue };
+                // Everything further along the list starts past the end of `a`
+                if aabb_b.min.x > aabb_a.max.x {
+                    break;
+                }
+                if aabb_a.overlaps(&aabb_b) {
+                    pairs.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    fn query_aabb(&self, aabb: &Aabb) -> Vec<usize> {
+        // The order may be stale after updates since the last sort, so check every proxy
+        let mut result: Vec<usize> = self.order.iter()
+            .copied()
+            .filter(|&index| self.aabbs[index].is_some_and(|other| other.overlaps(aabb)))
+            .collect();
+        result.sort_unstable();
+        result
+    }
+}
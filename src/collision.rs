@@ -1,105 +1,365 @@
 use crate::vector2d::Vector2D;
 use crate::rigidbody::RigidBody;
 use crate::shape::Shape;
+use crate::handle::BodyHandle;
 
-#[derive(Debug, Clone)]
-pub struct Contact {
+/// Slack (in pixels) before switching the SAT reference face from body A to body B
+const SAT_TOLERANCE: f32 = 0.05;
+/// Polygon contacts are kept up to this separation (pixels), so resting faces don't lose
+/// a manifold point to rounding and start rocking
+pub(crate) const CONTACT_MARGIN: f32 = 0.5;
+
+/// Which bodies a body may collide with, same scheme as Box2D.
+/// Bodies in the same non-zero group always collide (positive group) or never do (negative group),
+/// otherwise each body's category has to be in the other's mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionFilter {
+    /// Categories the body belongs to, usually a single bit
+    pub category_bits: u16,
+    /// Categories the body collides with
+    pub mask_bits: u16,
+    pub group_index: i16,
+}
+
+impl Default for CollisionFilter {
+    /// In the first category, colliding with everything
+    fn default() -> Self {
+        Self { category_bits: 0x0001, mask_bits: 0xFFFF, group_index: 0 }
+    }
+}
+
+impl CollisionFilter {
+    pub fn should_collide(&self, other: &CollisionFilter) -> bool {
+        if self.group_index == other.group_index && self.group_index != 0 {
+            return self.group_index > 0;
+        }
+        self.mask_bits & other.category_bits != 0 && self.category_bits & other.mask_bits != 0
+    }
+}
+
+/// User test run on each pair that passes the bodies' filters, before narrowphase.
+/// Returning false skips the pair for this step.
+pub type PairFilter = Box<dyn Fn(BodyHandle, &RigidBody, BodyHandle, &RigidBody) -> bool>;
+
+/// One point of contact between two bodies
+#[derive(Debug, Clone, Copy)]
+pub struct ContactPoint {
     pub point: Vector2D,
-    pub normal: Vector2D,
+    /// Depth of the overlap, slightly negative for points kept within the contact margin
     pub penetration: f32,
-    pub body_a_index: usize,
-    pub body_b_index: usize,
+    /// Identifies the pair of features (faces / vertices) that produced the point.
+    /// Stays the same between frames while the same features touch, used for warm starting.
+    pub id: u32,
+}
+
+/// All contact points between a pair of bodies, sharing one normal (from A to B).
+/// Holds one point for curved shapes and up to two for face-to-face polygon contacts.
+#[derive(Debug, Clone)]
+pub struct ContactManifold {
+    pub normal: Vector2D,
+    pub points: Vec<ContactPoint>,
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+}
+
+impl ContactManifold {
+    /// Whether the shapes actually overlap, rather than only being within the contact margin
+    pub fn is_overlapping(&self) -> bool {
+        self.points.iter().any(|contact| contact.penetration >= 0.0)
+    }
 }
 
 pub fn detect_collision(body_a: &RigidBody, body_b: &RigidBody,
-                        index_a: usize, index_b: usize) -> Option<Contact> {
-    match (&body_a.shape, &body_b.shape) {
+                        handle_a: BodyHandle, handle_b: BodyHandle) -> Option<ContactManifold> {
+    detect_collision_within(body_a, body_b, handle_a, handle_b, 0.0)
+}
+
+/// Like `detect_collision`, but curved shapes also keep contacts up to `margin` apart the way polygons do.
+/// Bullets stop just short of what they hit and still need a manifold there.
+pub(crate) fn detect_collision_within(body_a: &RigidBody, body_b: &RigidBody,
+                                      handle_a: BodyHandle, handle_b: BodyHandle, margin: f32) -> Option<ContactManifold> {
+    let (normal, points) = match (&body_a.shape, &body_b.shape) {
         // (Shape::Circle { radius: r1, center: _ }, Shape::Circle { radius: r2, center: _ }) => {
         //     circle_circle_collision(body_a, body_b, *r1, *r2, index_a, index_b)
         // }
         (Shape::Circle { radius: r1 }, Shape::Circle { radius: r2, }) => {
-            circle_circle_collision(body_a, body_b, *r1, *r2, index_a, index_b)
+            single_point(circle_circle_collision(body_a, body_b, *r1, *r2, margin)?)
         }
         ,
-        (Shape::Rectangle { .. }, Shape::Rectangle { .. }) => {
-            // Simplified AABB collision for rectangles
-            aabb_collition(body_a, body_b, index_a, index_b)
+        (Shape::Circle { radius }, Shape::Rectangle { width, height }) => {
+            // Normal comes back pointing from the rectangle to the circle, flip it to keep A -> B
+            let (normal, points) = single_point(circle_rectangle_collision(body_a, body_b, *radius, *width, *height, margin)?);
+            (normal * -1.0, points)
         },
-        _ => { 
-            // Circle-Rectangle collision (simplified)
-            None // TODO: Implement Circle-Rectangle collision
-        }
-    }
+        (Shape::Rectangle { width, height }, Shape::Circle { radius }) => {
+            single_point(circle_rectangle_collision(body_b, body_a, *radius, *width, *height, margin)?)
+        },
+        (Shape::Circle { radius }, Shape::Polygon { vertices }) => {
+            let (normal, points) = single_point(circle_polygon_collision(body_a, body_b, *radius, vertices, margin)?);
+            (normal * -1.0, points)
+        },
+        (Shape::Polygon { vertices }, Shape::Circle { radius }) => {
+            single_point(circle_polygon_collision(body_b, body_a, *radius, vertices, margin)?)
+        },
+        (Shape::Rectangle { .. } | Shape::Polygon { .. }, Shape::Rectangle { .. } | Shape::Polygon { .. }) => {
+            // Oriented boxes and convex polygons, separating axis test using each body's angle
+            sat_collision(&world_vertices(body_a), &world_vertices(body_b))?
+        },
+    };
+
+    Some(ContactManifold {
+        normal,
+        points,
+        body_a: handle_a,
+        body_b: handle_b,
+    })
+}
+
+/// Wraps a (point, normal, penetration) result from the curved shape tests
+fn single_point((point, normal, penetration): (Vector2D, Vector2D, f32)) -> (Vector2D, Vec<ContactPoint>) {
+    (normal, vec![ContactPoint { point, penetration, id: 0 }])
 }
 
 fn circle_circle_collision(body_a: &RigidBody, body_b: &RigidBody,
-                            r1: f32, r2: f32, index_a: usize, index_b: usize) -> Option<Contact> {
+                            r1: f32, r2: f32, margin: f32) -> Option<(Vector2D, Vector2D, f32)> {
     
     let distance_vec = body_b.position - body_a.position;
     let distance = distance_vec.magnitude();
     let radii_sum = r1 + r2;
 
-    if distance < radii_sum && distance > 0.0 {
+    if distance < radii_sum + margin && distance > 0.0 {
         let normal = distance_vec.normalize();
         let penetration = radii_sum - distance;
         let contact_point = body_a.position + normal * r1;
 
-        Some(Contact {
-            point: contact_point,
-            normal,
-            penetration,
-            body_a_index: index_a,
-            body_b_index: index_b,
-        })
+        Some((contact_point, normal, penetration))
     } else {
         None
     }
 }
 
-fn aabb_collition(body_a: &RigidBody, body_b: &RigidBody,
-                index_a: usize, index_b: usize) -> Option<Contact> {
-    let (w1, h1) = match &body_a.shape {
-        Shape::Rectangle { width, height } => (*width, *height),
-        _ => return None,
+/// Circle against an oriented rectangle (uses `rect.angle`).
+/// Returns (contact point on the rectangle surface, normal from rectangle to circle, penetration).
+fn circle_rectangle_collision(circle: &RigidBody, rect: &RigidBody,
+                              radius: f32, width: f32, height: f32, margin: f32) -> Option<(Vector2D, Vector2D, f32)> {
+    let half_w = width / 2.0;
+    let half_h = height / 2.0;
+
+    // Move the circle center into the rectangle's local frame
+    let local = (circle.position - rect.position).rotate(-rect.angle);
+
+    let closest = Vector2D::new(
+        local.x.clamp(-half_w, half_w),
+        local.y.clamp(-half_h, half_h),
+    );
+
+    let (local_normal, local_point, penetration) = if closest == local {
+        // Center is inside the rectangle, push out through the nearest face
+        let dist_x = half_w - local.x.abs();
+        let dist_y = half_h - local.y.abs();
+        if dist_x < dist_y {
+            let sign = if local.x < 0.0 { -1.0 } else { 1.0 };
+            (Vector2D::new(sign, 0.0), Vector2D::new(sign * half_w, local.y), radius + dist_x)
+        } else {
+            let sign = if local.y < 0.0 { -1.0 } else { 1.0 };
+            (Vector2D::new(0.0, sign), Vector2D::new(local.x, sign * half_h), radius + dist_y)
+        }
+    } else {
+        let delta = local - closest;
+        let distance_squared = delta.magnitude_squared();
+        if distance_squared >= (radius + margin) * (radius + margin) {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        (delta / distance, closest, radius - distance)
+    };
+
+    let normal = local_normal.rotate(rect.angle);
+    let point = rect.position + local_point.rotate(rect.angle);
+    Some((point, normal, penetration))
+}
+
+/// Circle against a convex polygon (uses `polygon.angle`).
+/// Returns (contact point on the polygon surface, normal from polygon to circle, penetration).
+fn circle_polygon_collision(circle: &RigidBody, polygon: &RigidBody,
+                            radius: f32, vertices: &[Vector2D], margin: f32) -> Option<(Vector2D, Vector2D, f32)> {
+    // Move the circle center into the polygon's local frame
+    let local = (circle.position - polygon.position).rotate(-polygon.angle);
+
+    // Face the center is furthest out of
+    let (face, separation) = max_separation(vertices, &[local]);
+    if separation > radius + margin {
+        return None;
+    }
+
+    let face_normal = edge_normal(vertices, face);
+    let v1 = vertices[face];
+    let v2 = vertices[(face + 1) % vertices.len()];
+
+    let (local_normal, local_point, penetration) = if separation <= 0.0 {
+        // Center is inside the polygon, push out through the nearest face
+        (face_normal, local - face_normal * separation, radius - separation)
+    } else {
+        // Closest point on the face segment, which may be one of its vertices
+        let edge = v2 - v1;
+        let t = ((local - v1).dot(&edge) / edge.magnitude_squared()).clamp(0.0, 1.0);
+        let closest = v1 + edge * t;
+        let delta = local - closest;
+        let distance_squared = delta.magnitude_squared();
+        if distance_squared >= (radius + margin) * (radius + margin) {
+            return None;
+        }
+        let distance = distance_squared.sqrt();
+        let normal = if distance > f32::EPSILON { delta / distance } else { face_normal };
+        (normal, closest, radius - distance)
     };
 
-    let (w2, h2) = match &body_b.shape {
-        Shape::Rectangle { width, height } => (*width, *height),
-        _ => return None,
+    let normal = local_normal.rotate(polygon.angle);
+    let point = polygon.position + local_point.rotate(polygon.angle);
+    Some((point, normal, penetration))
+}
+
+/// World space vertices of a rectangle or polygon body in counter-clockwise order
+fn world_vertices(body: &RigidBody) -> Vec<Vector2D> {
+    body.shape.vertices()
+        .into_iter()
+        .map(|v| body.position + v.rotate(body.angle))
+        .collect()
+}
+
+/// Outward normal of the edge starting at vertex `i` (counter-clockwise winding)
+pub(crate) fn edge_normal(vertices: &[Vector2D], i: usize) -> Vector2D {
+    let edge = vertices[(i + 1) % vertices.len()] - vertices[i];
+    Vector2D::new(edge.y, -edge.x).normalize()
+}
+
+/// Finds the face of `a` that `b` is furthest out of.
+/// Returns the face index and the separation along its normal (negative when overlapping).
+pub(crate) fn max_separation(a: &[Vector2D], b: &[Vector2D]) -> (usize, f32) {
+    let mut best_face = 0;
+    let mut best_separation = f32::MIN;
+
+    for i in 0..a.len() {
+        let normal = edge_normal(a, i);
+        let separation = b.iter()
+            .map(|v| normal.dot(&(*v - a[i])))
+            .fold(f32::MAX, f32::min);
+
+        if separation > best_separation {
+            best_separation = separation;
+            best_face = i;
+        }
+    }
+
+    (best_face, best_separation)
+}
+
+/// Incident edge end point tracked through clipping so each contact gets a feature id
+#[derive(Debug, Clone, Copy)]
+struct ClipVertex {
+    point: Vector2D,
+    // Incident vertex this point stands for, a clipped point takes over the id of the vertex it cut away
+    incident_vertex: usize,
+}
+
+/// Keeps the part of the segment that lies on the negative side of the plane `normal . p = offset`
+fn clip_segment(segment: &[ClipVertex], normal: Vector2D, offset: f32) -> Vec<ClipVertex> {
+    let mut out = Vec::with_capacity(2);
+    let distance_0 = normal.dot(&segment[0].point) - offset;
+    let distance_1 = normal.dot(&segment[1].point) - offset;
+
+    if distance_0 <= 0.0 {
+        out.push(segment[0]);
+    }
+    if distance_1 <= 0.0 {
+        out.push(segment[1]);
+    }
+    // Endpoints on opposite sides, add the intersection point
+    if distance_0 * distance_1 < 0.0 {
+        let t = distance_0 / (distance_0 - distance_1);
+        let removed = if distance_0 > 0.0 { segment[0] } else { segment[1] };
+        out.push(ClipVertex {
+            point: segment[0].point + (segment[1].point - segment[0].point) * t,
+            incident_vertex: removed.incident_vertex,
+        });
+    }
+    out
+}
+
+/// Packs the features of a clipped contact into a `ContactPoint::id`.
+/// Ignores whether the point was clipped, so the id survives corners sliding across the face ends.
+fn feature_id(reference_face: usize, vertex: &ClipVertex, flip: bool) -> u32 {
+    ((flip as u32) << 16)
+        | ((reference_face as u32 & 0xFF) << 8)
+        | (vertex.incident_vertex as u32 & 0xFF)
+}
+
+/// Separating axis test between two convex vertex loops (counter-clockwise, world space).
+/// The incident face is clipped against the reference face, giving up to two contact points.
+/// Returns (normal from A to B, contact points).
+fn sat_collision(vertices_a: &[Vector2D], vertices_b: &[Vector2D]) -> Option<(Vector2D, Vec<ContactPoint>)> {
+    let (face_a, separation_a) = max_separation(vertices_a, vertices_b);
+    if separation_a > CONTACT_MARGIN {
+        return None;
+    }
+
+    let (face_b, separation_b) = max_separation(vertices_b, vertices_a);
+    if separation_b > CONTACT_MARGIN {
+        return None;
+    }
+
+    // Prefer A as the reference polygon so the chosen face does not flip between frames
+    let (reference, incident, reference_face, flip) = if separation_b > separation_a + SAT_TOLERANCE {
+        (vertices_b, vertices_a, face_b, true)
+    } else {
+        (vertices_a, vertices_b, face_a, false)
     };
 
-    let dx = body_b.position.x - body_a.position.x;
-    let dy = body_b.position.y - body_a.position.y;
-
-    let overlap_x = ( w1 + w2 ) / 2.0 - dx.abs();
-    let overlap_y = ( h1 + h2 ) / 2.0 - dy.abs();
-
-    if overlap_x > 0.0 && overlap_y > 0.0 {
-        let (normal, penetration, contact_point) = if overlap_x < overlap_y {
-            let normal = Vector2D::new(if dx > 0.0 {-1.0} else { 1.0 }, 0.0);
-            let contact_x = if dx < 0.0 {
-                body_a.position.x + w1 / 2.0
-            } else {
-                body_a.position.x - w1 / 2.0
-            };
-            (normal, overlap_x, Vector2D::new(contact_x, body_a.position.y))
-        } else {
-           let normal = Vector2D::new(0.0, if dy > 0.0 {-1.0} else { 1.0 });
-           let contact_y = if dy > 0.0 {
-                body_a.position.y + h1 / 2.0
-            } else {
-                body_a.position.y - h1 /2.0
-            };
-            (normal, overlap_y, Vector2D::new(body_a.position.x, contact_y))
-        };
-        Some(Contact {
-            point: contact_point,
-            normal,
-            penetration,
-            body_a_index: index_a,
-            body_b_index: index_b,
+    let reference_normal = edge_normal(reference, reference_face);
+    let v1 = reference[reference_face];
+    let v2 = reference[(reference_face + 1) % reference.len()];
+
+    // Incident face is the one most anti-parallel to the reference normal
+    let incident_face = (0..incident.len())
+        .min_by(|&i, &j| {
+            let di = edge_normal(incident, i).dot(&reference_normal);
+            let dj = edge_normal(incident, j).dot(&reference_normal);
+            di.total_cmp(&dj)
         })
-    } else {
-        None
+        .unwrap_or(0);
+    let incident_next = (incident_face + 1) % incident.len();
+    let incident_edge = [
+        ClipVertex { point: incident[incident_face], incident_vertex: incident_face },
+        ClipVertex { point: incident[incident_next], incident_vertex: incident_next },
+    ];
+
+    // Clip the incident edge to the side planes of the reference face
+    let tangent = (v2 - v1).normalize();
+    let clipped = clip_segment(&incident_edge, tangent * -1.0, -tangent.dot(&v1));
+    if clipped.len() < 2 {
+        return None;
+    }
+    let clipped = clip_segment(&clipped, tangent, tangent.dot(&v2));
+    if clipped.len() < 2 {
+        return None;
     }
-}
\ No newline at end of file
+
+    // Keep the clipped points that are behind (or within the margin of) the reference face, each with its own depth
+    let points: Vec<ContactPoint> = clipped.iter()
+        .filter_map(|vertex| {
+            let separation = reference_normal.dot(&(vertex.point - v1));
+            (separation <= CONTACT_MARGIN).then(|| ContactPoint {
+                point: vertex.point,
+                penetration: -separation,
+                id: feature_id(reference_face, vertex, flip),
+            })
+        })
+        .collect();
+
+    if points.is_empty() {
+        return None;
+    }
+
+    let normal = if flip { reference_normal * -1.0 } else { reference_normal };
+    Some((normal, points))
+}
@@ -0,0 +1,453 @@
+use crate::aabb::Aabb;
+use crate::broadphase::BroadPhase;
+use crate::vector2d::Vector2D;
+
+const NULL_NODE: usize = usize::MAX;
+/// Fat AABB margin (pixels), a body can move this far before its leaf has to be reinserted
+const AABB_MARGIN: f32 = 4.0;
+
+#[derive(Debug, Clone)]
+struct TreeNode {
+    // Fat box for leaves, union of the children for internal nodes
+    aabb: Aabb,
+    parent: usize,
+    child1: usize,
+    child2: usize,
+    // 0 for leaves
+    height: i32,
+    // Leaf only: the body and its actual bounding box
+    body: usize,
+    tight: Aabb,
+}
+
+impl TreeNode {
+    fn is_leaf(&self) -> bool {
+        self.child1 == NULL_NODE
+    }
+}
+
+/// Dynamic bounding volume tree.
+/// Leaves hold enlarged ("fat") boxes so small movements don't touch the tree,
+/// inserts pick the sibling with the smallest perimeter cost and the tree is kept
+/// balanced with AVL style rotations. Handles mixes of huge and tiny bodies well.
+#[derive(Debug, Default)]
+pub struct DynamicTree {
+    nodes: Vec<TreeNode>,
+    root: Option<usize>,
+    free_nodes: Vec<usize>,
+    // Body index -> leaf node
+    leaves: Vec<Option<usize>>,
+}
+
+impl DynamicTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Height of the tree, 0 for a single leaf
+    pub fn height(&self) -> i32 {
+        self.root.map_or(0, |root| self.nodes[root].height)
+    }
+
+    fn allocate(&mut self, node: TreeNode) -> usize {
+        match self.free_nodes.pop() {
+            Some(id) => {
+                self.nodes[id] = node;
+                id
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn free(&mut self, id: usize) {
+        self.nodes[id].height = -1;
+        self.free_nodes.push(id);
+    }
+
+    /// Cost of the perimeter growth when `aabb` is added under `node`
+    fn descend_cost(&self, node: usize, aabb: &Aabb, inheritance: f32) -> f32 {
+        let node = &self.nodes[node];
+        let combined = node.aabb.union(aabb).perimeter();
+        if node.is_leaf() {
+            combined + inheritance
+        } else {
+            combined - node.aabb.perimeter() + inheritance
+        }
+    }
+
+    fn insert_leaf(&mut self, leaf: usize) {
+        let Some(root) = self.root else {
+            self.nodes[leaf].parent = NULL_NODE;
+            self.root = Some(leaf);
+            return;
+        };
+
+        // Find the best sibling, descending while it's cheaper than pairing with the current node
+        let leaf_aabb = self.nodes[leaf].aabb;
+        let mut index = root;
+        while !self.nodes[index].is_leaf() {
+            let node = &self.nodes[index];
+            let (child1, child2) = (node.child1, node.child2);
+            let area = node.aabb.perimeter();
+            let combined = node.aabb.union(&leaf_aabb).perimeter();
+
+            // Cost of a new parent for this node and the leaf
+            let cost = 2.0 * combined;
+            // Minimum cost of pushing the leaf further down the tree
+            let inheritance = 2.0 * (combined - area);
+
+            let cost1 = self.descend_cost(child1, &leaf_aabb, inheritance);
+            let cost2 = self.descend_cost(child2, &leaf_aabb, inheritance);
+            if cost < cost1 && cost < cost2 {
+                break;
+            }
+            index = if cost1 < cost2 { child1 } else { child2 };
+        }
+
+        // New parent for the sibling and the leaf
+        let sibling = index;
+        let old_parent = self.nodes[sibling].parent;
+        let new_parent = self.allocate(TreeNode {
+            aabb: self.nodes[sibling].aabb.union(&leaf_aabb),
+            parent: old_parent,
+            child1: sibling,
+            child2: leaf,
+            height: self.nodes[sibling].height + 1,
+            body: 0,
+            tight: leaf_aabb,
+        });
+        self.replace_child(old_parent, sibling, new_parent);
+        self.nodes[sibling].parent = new_parent;
+        self.nodes[leaf].parent = new_parent;
+
+        self.refit(new_parent);
+    }
+
+    fn remove_leaf(&mut self, leaf: usize) {
+        if self.root == Some(leaf) {
+            self.root = None;
+            return;
+        }
+
+        let parent = self.nodes[leaf].parent;
+        let grand_parent = self.nodes[parent].parent;
+        let sibling = if self.nodes[parent].child1 == leaf {
+            self.nodes[parent].child2
+        } else {
+            self.nodes[parent].child1
+        };
+
+        // The sibling takes the place of the parent
+        self.replace_child(grand_parent, parent, sibling);
+        self.nodes[sibling].parent = grand_parent;
+        self.free(parent);
+
+        if grand_parent != NULL_NODE {
+            self.refit(grand_parent);
+        }
+    }
+
+    /// Points `parent` (or the root, for `NULL_NODE`) at `new_child` instead of `old_child`
+    fn replace_child(&mut self, parent: usize, old_child: usize, new_child: usize) {
+        if parent == NULL_NODE {
+            self.root = Some(new_child);
+        } else if self.nodes[parent].child1 == old_child {
+            self.nodes[parent].child1 = new_child;
+        } else {
+            self.nodes[parent].child2 = new_child;
+        }
+    }
+
+    /// Walks up from `index` rebalancing and fixing heights and boxes
+    fn refit(&mut self, mut index: usize) {
+        while index != NULL_NODE {
+            index = self.balance(index);
+
+            let (child1, child2) = (self.nodes[index].child1, self.nodes[index].child2);
+            self.nodes[index].height = 1 + self.nodes[child1].height.max(self.nodes[child2].height);
+            self.nodes[index].aabb = self.nodes[child1].aabb.union(&self.nodes[child2].aabb);
+
+            index = self.nodes[index].parent;
+        }
+    }
+
+    /// Rotates the taller grandchild up if node `a` is imbalanced.
+    /// Returns the node now at the position of `a`.
+    fn balance(&mut self, a: usize) -> usize {
+        if self.nodes[a].is_leaf() || self.nodes[a].height < 2 {
+            return a;
+        }
+
+        let (b, c) = (self.nodes[a].child1, self.nodes[a].child2);
+        let balance = self.nodes[c].height - self.nodes[b].height;
+
+        if balance > 1 {
+            // Rotate C up, A takes C's shorter child
+            let (f, g) = (self.nodes[c].child1, self.nodes[c].child2);
+            self.rotate_up(a, c);
+            let (taller, shorter) = if self.nodes[f].height > self.nodes[g].height { (f, g) } else { (g, f) };
+            self.nodes[c].child2 = taller;
+            self.nodes[a].child2 = shorter;
+            self.nodes[shorter].parent = a;
+            self.fit_rotation(a, b, shorter, c, taller);
+            return c;
+        }
+
+        if balance < -1 {
+            // Rotate B up, A takes B's shorter child
+            let (d, e) = (self.nodes[b].child1, self.nodes[b].child2);
+            self.rotate_up(a, b);
+            let (taller, shorter) = if self.nodes[d].height > self.nodes[e].height { (d, e) } else { (e, d) };
+            self.nodes[b].child2 = taller;
+            self.nodes[a].child1 = shorter;
+            self.nodes[shorter].parent = a;
+            self.fit_rotation(a, c, shorter, b, taller);
+            return b;
+        }
+
+        a
+    }
+
+    /// Makes `child` the parent of `a`, in `a`'s place
+    fn rotate_up(&mut self, a: usize, child: usize) {
+        let parent = self.nodes[a].parent;
+        self.nodes[child].child1 = a;
+        self.nodes[child].parent = parent;
+        self.nodes[a].parent = child;
+        self.replace_child(parent, a, child);
+    }
+
+    /// Recomputes boxes and heights after a rotation: `a` holds `kept` and `moved`,
+    /// `top` holds `a` and `other`
+    fn fit_rotation(&mut self, a: usize, kept: usize, moved: usize, top: usize, other: usize) {
+        self.nodes[a].aabb = self.nodes[kept].aabb.union(&self.nodes[moved].aabb);
+        self.nodes[a].height = 1 + self.nodes[kept].height.max(self.nodes[moved].height);
+        self.nodes[top].aabb = self.nodes[a].aabb.union(&self.nodes[other].aabb);
+        self.nodes[top].height = 1 + self.nodes[a].height.max(self.nodes[other].height);
+    }
+
+    /// Calls `visit` for every leaf whose fat box overlaps `aabb`
+    fn query(&self, aabb: &Aabb, visit: impl FnMut(&TreeNode)) {
+        self.traverse(|node_aabb| node_aabb.overlaps(aabb), visit);
+    }
+
+    /// Visits the leaves below every node whose fat box passes `enter`
+    fn traverse(&self, enter: impl Fn(&Aabb) -> bool, mut visit: impl FnMut(&TreeNode)) {
+        let Some(root) = self.root else { return };
+        let mut stack = vec![root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !enter(&node.aabb) {
+                continue;
+            }
+            if node.is_leaf() {
+                visit(node);
+            } else {
+                stack.push(node.child1);
+                stack.push(node.child2);
+            }
+        }
+    }
+}
+
+impl BroadPhase for DynamicTree {
+    fn update(&mut self, index: usize, aabb: Aabb) {
+        if index >= self.leaves.len() {
+            self.leaves.resize(index + 1, None);
+        }
+
+        if let Some(leaf) = self.leaves[index] {
+            self.nodes[leaf].tight = aabb;
+            // Still inside the fat box, the tree doesn't change
+            if self.nodes[leaf].aabb.contains(&aabb) {
+                return;
+            }
+            self.remove_leaf(leaf);
+            self.nodes[leaf].aabb = aabb.expanded(AABB_MARGIN);
+            self.insert_leaf(leaf);
+        } else {
+            let leaf = self.allocate(TreeNode {
+                aabb: aabb.expanded(AABB_MARGIN),
+                parent: NULL_NODE,
+                child1: NULL_NODE,
+                child2: NULL_NODE,
+                height: 0,
+                body: index,
+                tight: aabb,
+            });
+            self.leaves[index] = Some(leaf);
+            self.insert_leaf(leaf);
+        }
+    }
+
+    fn remove(&mut self, index: usize) {
+        if let Some(leaf) = self.leaves.get_mut(index).and_then(Option::take) {
+            self.remove_leaf(leaf);
+            self.free(leaf);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.root = None;
+        self.free_nodes.clear();
+        self.leaves.clear();
+    }
+
+    fn collect_pairs(&mut self) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for leaf in self.leaves.iter().flatten() {
+            let node = &self.nodes[*leaf];
+            let (body, tight) = (node.body, node.tight);
+            self.query(&tight, |other| {
+                // Each pair is found from both sides, keep it once
+                if other.body > body && other.tight.overlaps(&tight) {
+                    pairs.push((body, other.body));
+                }
+            });
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    // Only queries around the active bodies, a tree full of sleeping bodies costs next to nothing
+    fn collect_active_pairs(&mut self, is_active: &dyn Fn(usize) -> bool) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for leaf in self.leaves.iter().flatten() {
+            let node = &self.nodes[*leaf];
+            let (body, tight) = (node.body, node.tight);
+            if !is_active(body) {
+                continue;
+            }
+            self.query(&tight, |other| {
+                // A pair of two active bodies is found from both sides, keep it once
+                let found_twice = is_active(other.body) && other.body < body;
+                if other.body != body && !found_twice && other.tight.overlaps(&tight) {
+                    pairs.push((body.min(other.body), body.max(other.body)));
+                }
+            });
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    fn query_aabb(&self, aabb: &Aabb) -> Vec<usize> {
+        let mut result = Vec::new();
+        self.query(aabb, |leaf| {
+            if leaf.tight.overlaps(aabb) {
+                result.push(leaf.body);
+            }
+        });
+        result.sort_unstable();
+        result
+    }
+
+    fn query_ray(&self, origin: Vector2D, direction: Vector2D, max_distance: f32) -> Vec<usize> {
+        let mut result = Vec::new();
+        let hits = |aabb: &Aabb| aabb.raycast(origin, direction, max_distance).is_some();
+        self.traverse(hits, |leaf| {
+            if hits(&leaf.tight) {
+                result.push(leaf.body);
+            }
+        });
+        result.sort_unstable();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Small deterministic generator, the same sequence on every run
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self) -> f32 {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (self.0 >> 40) as f32 / (1u64 << 24) as f32
+        }
+
+        fn aabb(&mut self) -> Aabb {
+            let min = Vector2D::new(self.next() * 1000.0, self.next() * 1000.0);
+            let size = Vector2D::new(1.0 + self.next() * 60.0, 1.0 + self.next() * 60.0);
+            Aabb::new(min, min + size)
+        }
+    }
+
+    /// Checks the links, heights and boxes of every node below `index`, returns the number of leaves
+    fn validate(tree: &DynamicTree, index: usize, parent: usize) -> usize {
+        let node = &tree.nodes[index];
+        assert_eq!(node.parent, parent, "node {index} has the wrong parent");
+        if node.is_leaf() {
+            assert_eq!(node.height, 0);
+            assert_eq!(tree.leaves[node.body], Some(index));
+            assert!(node.aabb.contains(&node.tight));
+            return 1;
+        }
+
+        let (child1, child2) = (&tree.nodes[node.child1], &tree.nodes[node.child2]);
+        assert_eq!(node.height, 1 + child1.height.max(child2.height), "node {index} has the wrong height");
+        assert!(node.aabb.contains(&child1.aabb) && node.aabb.contains(&child2.aabb));
+        validate(tree, node.child1, index) + validate(tree, node.child2, index)
+    }
+
+    fn brute_force_pairs(aabbs: &[Option<Aabb>]) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for (a, aabb_a) in aabbs.iter().enumerate() {
+            for (b, aabb_b) in aabbs.iter().enumerate().skip(a + 1) {
+                if let (Some(aabb_a), Some(aabb_b)) = (aabb_a, aabb_b)
+                    && aabb_a.overlaps(aabb_b)
+                {
+                    pairs.push((a, b));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn random_updates_keep_the_tree_valid_and_balanced() {
+        let mut rng = Lcg(7);
+        let mut tree = DynamicTree::new();
+        let mut aabbs: Vec<Option<Aabb>> = vec![None; 200];
+
+        for round in 0..2000 {
+            let index = (rng.next() * aabbs.len() as f32) as usize;
+            let roll = rng.next();
+            match aabbs[index] {
+                Some(_) if roll < 0.3 => {
+                    tree.remove(index);
+                    aabbs[index] = None;
+                }
+                Some(aabb) if roll < 0.7 => {
+                    // Small moves mostly stay inside the fat box, big ones reinsert the leaf
+                    let offset = Vector2D::new(rng.next() - 0.5, rng.next() - 0.5) * if roll < 0.5 { 4.0 } else { 200.0 };
+                    let moved = Aabb::new(aabb.min + offset, aabb.max + offset);
+                    tree.update(index, moved);
+                    aabbs[index] = Some(moved);
+                }
+                _ => {
+                    let aabb = rng.aabb();
+                    tree.update(index, aabb);
+                    aabbs[index] = Some(aabb);
+                }
+            }
+
+            if round % 50 == 0 {
+                let live = aabbs.iter().flatten().count();
+                match tree.root {
+                    Some(root) => assert_eq!(validate(&tree, root, NULL_NODE), live),
+                    None => assert_eq!(live, 0),
+                }
+                // An AVL balanced tree is at most about 1.44 log2(n) high
+                let bound = 1.45 * (live.max(2) as f32).log2() + 2.0;
+                assert!((tree.height() as f32) <= bound, "height {} for {live} leaves", tree.height());
+                assert_eq!(tree.collect_pairs(), brute_force_pairs(&aabbs));
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::collision::ContactManifold;
+use crate::handle::BodyHandle;
+use crate::solver::ImpulseCache;
+use crate::vector2d::Vector2D;
+
+/// Phase of a contact between two bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactEventKind {
+    /// The bodies started touching this step. Polygons and bullet impacts count as touching from
+    /// the contact margin on, up to half a pixel before they overlap, see `ContactManifold::is_overlapping`.
+    Begin,
+    /// The bodies were already touching last step and still are
+    Persist,
+    /// The bodies touched last step but not anymore (or one of them was removed)
+    End,
+}
+
+/// Contact between two bodies as reported by `World::drain_contact_events`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactEvent {
+    pub kind: ContactEventKind,
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    /// Contact normal, from A to B
+    pub normal: Vector2D,
+    /// Average of the manifold's contact points, in world space
+    pub point: Vector2D,
+    /// Total normal impulse the solver applied to separate the bodies this step, 0 for `End`.
+    /// Divide by the step size for the contact force, a hard hit gives a large spike.
+    pub impulse: f32,
+}
+
+impl ContactEvent {
+    pub(crate) fn new(kind: ContactEventKind, manifold: &ContactManifold, impulses: &ImpulseCache) -> Self {
+        let count = manifold.points.len().max(1) as f32;
+        let point = manifold.points.iter()
+            .fold(Vector2D::zero(), |sum, contact| sum + contact.point) / count;
+        let impulse = match kind {
+            ContactEventKind::End => 0.0,
+            _ => manifold.points.iter()
+                .filter_map(|contact| impulses.get(&(manifold.body_a, manifold.body_b, contact.id)))
+                .map(|(normal_impulse, _)| normal_impulse)
+                .sum(),
+        };
+        Self {
+            kind,
+            body_a: manifold.body_a,
+            body_b: manifold.body_b,
+            normal: manifold.normal,
+            point,
+            impulse,
+        }
+    }
+}
+
+/// Whether a body started or stopped overlapping a sensor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SensorEventKind {
+    Enter,
+    /// Also reported when one of the two is removed
+    Exit,
+}
+
+/// Overlap change of a sensor body as reported by `World::drain_sensor_events`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SensorEvent {
+    pub kind: SensorEventKind,
+    pub sensor: BodyHandle,
+    /// Body entering or leaving the sensor, may be a sensor itself
+    pub visitor: BodyHandle,
+}
@@ -0,0 +1,65 @@
+/// Stable reference to a body in a `World`.
+/// Stays valid while the body exists, and never refers to another body once it's removed:
+/// the slot's generation is bumped on removal, so stale handles simply stop resolving.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BodyHandle {
+    index: u32,
+    generation: u32,
+}
+
+impl BodyHandle {
+    pub(crate) fn new(index: usize, generation: u32) -> Self {
+        Self { index: index as u32, generation }
+    }
+
+    /// Slot of the body, reused by later bodies once this one is removed
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// Stable reference to a joint in a `World`, invalidated the same way as `BodyHandle`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct JointHandle {
+    index: u32,
+    generation: u32,
+}
+
+impl JointHandle {
+    pub(crate) fn new(index: usize, generation: u32) -> Self {
+        Self { index: index as u32, generation }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// Stable reference to a spring in a `World`, invalidated the same way as `BodyHandle`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SpringHandle {
+    index: u32,
+    generation: u32,
+}
+
+impl SpringHandle {
+    pub(crate) fn new(index: usize, generation: u32) -> Self {
+        Self { index: index as u32, generation }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
@@ -0,0 +1,50 @@
+use crate::vector2d::Vector2D;
+
+/// Acceleration field evaluated at a body's (position, velocity), e.g. a gravity well or wind.
+/// Returns force per unit mass so it can be sampled at the intermediate states of RK4.
+pub type ForceField = Box<dyn Fn(Vector2D, Vector2D) -> Vector2D>;
+
+/// How `World::step` advances velocities and positions.
+/// Contacts are solved between the velocity and the position update for all of them.
+/// Rotation always uses semi-implicit Euler, torques are constant over a step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Integrator {
+    /// Velocity first, then position with the new velocity. Cheap and stable
+    #[default]
+    SemiImplicitEuler,
+    /// Half velocity kick, position drift, then a second half kick with the acceleration
+    /// at the new position. Second order and energy conserving for position dependent forces
+    VelocityVerlet,
+    /// Classic fourth order Runge-Kutta on position and velocity,
+    /// most accurate in smooth force fields but samples them four times per step
+    Rk4,
+}
+
+/// Fraction of the velocity left after damping with `coefficient` (1/s) for `dt` seconds.
+/// Exact solution of `dv/dt = -coefficient * v`, so the decay doesn't depend on the step size.
+pub(crate) fn damping_factor(coefficient: f32, dt: f32) -> f32 {
+    (-coefficient * dt).exp()
+}
+
+/// One RK4 step of `x' = v, v' = acceleration(x, v)`, returns the new (position, velocity)
+pub(crate) fn rk4(position: Vector2D, velocity: Vector2D, dt: f32,
+                  acceleration: impl Fn(Vector2D, Vector2D) -> Vector2D) -> (Vector2D, Vector2D) {
+    let half_dt = dt / 2.0;
+
+    let k1_x = velocity;
+    let k1_v = acceleration(position, velocity);
+
+    let k2_x = velocity + k1_v * half_dt;
+    let k2_v = acceleration(position + k1_x * half_dt, k2_x);
+
+    let k3_x = velocity + k2_v * half_dt;
+    let k3_v = acceleration(position + k2_x * half_dt, k3_x);
+
+    let k4_x = velocity + k3_v * dt;
+    let k4_v = acceleration(position + k3_x * dt, k4_x);
+
+    (
+        position + (k1_x + k2_x * 2.0 + k3_x * 2.0 + k4_x) * (dt / 6.0),
+        velocity + (k1_v + k2_v * 2.0 + k3_v * 2.0 + k4_v) * (dt / 6.0),
+    )
+}
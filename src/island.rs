@@ -0,0 +1,44 @@
+/// Groups of bodies connected by contacts, joints or springs, which wake up and fall asleep together.
+/// Union-find over the positions of the bodies in `World::bodies`.
+pub(crate) struct Islands {
+    parent: Vec<usize>,
+    // (kinematic body, dynamic body) pairs, see `attach`
+    attached: Vec<(usize, usize)>,
+}
+
+impl Islands {
+    /// Every body on an island of its own
+    pub(crate) fn new(count: usize) -> Self {
+        Self { parent: (0..count).collect(), attached: Vec::new() }
+    }
+
+    /// Ties the island of `body` to the kinematic body at `kinematic` without merging the islands,
+    /// so everything riding one platform doesn't become a single island. The island stays awake
+    /// while the kinematic body moves.
+    pub(crate) fn attach(&mut self, kinematic: usize, body: usize) {
+        self.attached.push((kinematic, body));
+    }
+
+    /// The `(kinematic body, dynamic body)` pairs given to `attach`
+    pub(crate) fn attached(&self) -> Vec<(usize, usize)> {
+        self.attached.clone()
+    }
+
+    /// Merges the islands of `a` and `b`
+    pub(crate) fn join(&mut self, a: usize, b: usize) {
+        let (root_a, root_b) = (self.find(a), self.find(b));
+        if root_a != root_b {
+            self.parent[root_a] = root_b;
+        }
+    }
+
+    /// Representative body of the island `a` is on, the same for every body of that island
+    pub(crate) fn find(&mut self, mut a: usize) -> usize {
+        while self.parent[a] != a {
+            // Path halving keeps later lookups short
+            self.parent[a] = self.parent[self.parent[a]];
+            a = self.parent[a];
+        }
+        a
+    }
+}
@@ -0,0 +1,180 @@
+use crate::handle::BodyHandle;
+use crate::rigidbody::RigidBody;
+use crate::solver::{PositionState, VelocityState, LINEAR_SLOP, MAX_LINEAR_CORRECTION};
+use crate::vector2d::Vector2D;
+
+use super::{lever_arm, JointConstraint};
+
+/// Keeps two anchor points at a fixed distance (a rigid rod), or, with
+/// `min_length < max_length`, anywhere within that range (a rope for `min_length` 0).
+/// The joint only acts along the line between the anchors, so keep its length well above
+/// zero, a very short joint flips direction as the bodies rotate and becomes unstable.
+#[derive(Debug, Clone)]
+pub struct DistanceJoint {
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    /// Anchor points relative to each body's center, in the body's frame
+    pub local_anchor_a: Vector2D,
+    pub local_anchor_b: Vector2D,
+    pub min_length: f32,
+    pub max_length: f32,
+    pub collide_connected: bool,
+
+    // Solver state
+    impulse: f32,
+    lower_impulse: f32,
+    upper_impulse: f32,
+    r_a: Vector2D,
+    r_b: Vector2D,
+    axis: Vector2D,
+    current_length: f32,
+    mass: f32,
+}
+
+impl DistanceJoint {
+    /// Rigid rod of `length` between the anchors
+    pub fn new(body_a: BodyHandle, body_b: BodyHandle,
+               local_anchor_a: Vector2D, local_anchor_b: Vector2D, length: f32) -> Self {
+        Self {
+            body_a,
+            body_b,
+            local_anchor_a,
+            local_anchor_b,
+            min_length: length,
+            max_length: length,
+            collide_connected: false,
+            impulse: 0.0,
+            lower_impulse: 0.0,
+            upper_impulse: 0.0,
+            r_a: Vector2D::zero(),
+            r_b: Vector2D::zero(),
+            axis: Vector2D::zero(),
+            current_length: 0.0,
+            mass: 0.0,
+        }
+    }
+
+    /// Rope that can go slack but not stretch beyond `max_length`
+    pub fn rope(body_a: BodyHandle, body_b: BodyHandle,
+                local_anchor_a: Vector2D, local_anchor_b: Vector2D, max_length: f32) -> Self {
+        let mut joint = Self::new(body_a, body_b, local_anchor_a, local_anchor_b, max_length);
+        joint.min_length = 0.0;
+        joint
+    }
+
+    /// Net impulse along the joint axis last step, positive pushes the anchors apart
+    pub fn impulse(&self) -> f32 {
+        self.impulse + self.lower_impulse - self.upper_impulse
+    }
+
+    fn is_rigid(&self) -> bool {
+        self.min_length >= self.max_length
+    }
+}
+
+impl JointConstraint for DistanceJoint {
+    fn bodies(&self) -> (BodyHandle, BodyHandle) {
+        (self.body_a, self.body_b)
+    }
+
+    fn collide_connected(&self) -> bool {
+        self.collide_connected
+    }
+
+    fn local_anchors(&self) -> (Vector2D, Vector2D) {
+        (self.local_anchor_a, self.local_anchor_b)
+    }
+
+    fn prepare(&mut self, body_a: &RigidBody, body_b: &RigidBody, _dt: f32, warm_starting: bool) {
+        self.r_a = lever_arm(body_a, self.local_anchor_a);
+        self.r_b = lever_arm(body_b, self.local_anchor_b);
+        let offset = (body_b.position + self.r_b) - (body_a.position + self.r_a);
+
+        self.current_length = offset.magnitude();
+        self.axis = if self.current_length > LINEAR_SLOP {
+            offset / self.current_length
+        } else {
+            Vector2D::zero()
+        };
+
+        let cr_a = self.r_a.cross(&self.axis);
+        let cr_b = self.r_b.cross(&self.axis);
+        let inv_mass = body_a.inv_mass + body_b.inv_mass
+            + body_a.inv_moment_of_inertia * cr_a * cr_a
+            + body_b.inv_moment_of_inertia * cr_b * cr_b;
+        self.mass = if inv_mass > 0.0 { 1.0 / inv_mass } else { 0.0 };
+
+        if !warm_starting {
+            self.impulse = 0.0;
+            self.lower_impulse = 0.0;
+            self.upper_impulse = 0.0;
+        }
+    }
+
+    fn warm_start(&self, state: &mut VelocityState) {
+        state.apply(self.axis * self.impulse(), self.r_a, self.r_b);
+    }
+
+    fn solve_velocity(&mut self, state: &mut VelocityState, dt: f32) {
+        if self.is_rigid() {
+            let velocity = state.relative_velocity(self.r_a, self.r_b).dot(&self.axis);
+            let impulse = -self.mass * velocity;
+            self.impulse += impulse;
+            state.apply(self.axis * impulse, self.r_a, self.r_b);
+            return;
+        }
+
+        // Lower limit, may only push the anchors apart.
+        // While above the limit the bias lets the anchors close the gap within this step.
+        {
+            let gap = (self.current_length - self.min_length).max(0.0);
+            let velocity = state.relative_velocity(self.r_a, self.r_b).dot(&self.axis);
+            let impulse = -self.mass * (velocity + gap / dt);
+            let new_impulse = (self.lower_impulse + impulse).max(0.0);
+            let impulse = new_impulse - self.lower_impulse;
+            self.lower_impulse = new_impulse;
+            state.apply(self.axis * impulse, self.r_a, self.r_b);
+        }
+
+        // Upper limit, may only pull the anchors together
+        {
+            let gap = (self.max_length - self.current_length).max(0.0);
+            let velocity = -state.relative_velocity(self.r_a, self.r_b).dot(&self.axis);
+            let impulse = -self.mass * (velocity + gap / dt);
+            let new_impulse = (self.upper_impulse + impulse).max(0.0);
+            let impulse = new_impulse - self.upper_impulse;
+            self.upper_impulse = new_impulse;
+            state.apply(self.axis * -impulse, self.r_a, self.r_b);
+        }
+    }
+
+    fn solve_position(&self, state: &mut PositionState) -> bool {
+        let (r_a, r_b, offset) = state.anchor_offsets(self.local_anchor_a, self.local_anchor_b);
+        let length = offset.magnitude();
+        if length <= LINEAR_SLOP {
+            return true;
+        }
+        let axis = offset / length;
+
+        // A rigid joint is pulled back to its length from both sides
+        let error = if self.is_rigid() || length < self.min_length {
+            length - self.min_length
+        } else if length > self.max_length {
+            length - self.max_length
+        } else {
+            return true;
+        };
+        let error = error.clamp(-MAX_LINEAR_CORRECTION, MAX_LINEAR_CORRECTION);
+
+        let cr_a = r_a.cross(&axis);
+        let cr_b = r_b.cross(&axis);
+        let inv_mass = state.inv_mass_a + state.inv_mass_b
+            + state.inv_i_a * cr_a * cr_a
+            + state.inv_i_b * cr_b * cr_b;
+        if inv_mass > 0.0 {
+            state.apply(axis * (-error / inv_mass), r_a, r_b);
+        }
+
+        error.abs() < LINEAR_SLOP
+    }
+}
@@ -0,0 +1,205 @@
+mod distance;
+mod mouse;
+mod prismatic;
+mod revolute;
+mod weld;
+mod wheel;
+
+pub use distance::DistanceJoint;
+pub use mouse::MouseJoint;
+pub use prismatic::PrismaticJoint;
+pub use revolute::RevoluteJoint;
+pub use weld::WeldJoint;
+pub use wheel::WheelJoint;
+
+use crate::handle::BodyHandle;
+use crate::rigidbody::RigidBody;
+use crate::solver::{Mat22, PositionState, VelocityState};
+use crate::vector2d::Vector2D;
+
+/// Constraint between two bodies, solved by the world together with the contacts
+#[derive(Debug, Clone)]
+pub enum Joint {
+    Distance(DistanceJoint),
+    Revolute(RevoluteJoint),
+    Prismatic(PrismaticJoint),
+    Weld(WeldJoint),
+    Wheel(WheelJoint),
+    Mouse(MouseJoint),
+}
+
+impl Joint {
+    /// The two connected bodies
+    pub fn bodies(&self) -> (BodyHandle, BodyHandle) {
+        self.constraint().bodies()
+    }
+
+    /// Anchor points relative to each body's center, in the body's frame.
+    /// A mouse joint has no anchor on A, its first point is the world space target.
+    pub fn local_anchors(&self) -> (Vector2D, Vector2D) {
+        self.constraint().local_anchors()
+    }
+
+    /// World space anchor points on body A and body B, the target for a mouse joint
+    pub fn anchors(&self, body_a: &RigidBody, body_b: &RigidBody) -> (Vector2D, Vector2D) {
+        let (local_a, local_b) = self.local_anchors();
+        match self {
+            Joint::Mouse(joint) => (joint.target, body_b.world_point(local_b)),
+            _ => (body_a.world_point(local_a), body_b.world_point(local_b)),
+        }
+    }
+
+    pub(crate) fn constraint(&self) -> &dyn JointConstraint {
+        match self {
+            Joint::Distance(joint) => joint,
+            Joint::Revolute(joint) => joint,
+            Joint::Prismatic(joint) => joint,
+            Joint::Weld(joint) => joint,
+            Joint::Wheel(joint) => joint,
+            Joint::Mouse(joint) => joint,
+        }
+    }
+
+    pub(crate) fn constraint_mut(&mut self) -> &mut dyn JointConstraint {
+        match self {
+            Joint::Distance(joint) => joint,
+            Joint::Revolute(joint) => joint,
+            Joint::Prismatic(joint) => joint,
+            Joint::Weld(joint) => joint,
+            Joint::Wheel(joint) => joint,
+            Joint::Mouse(joint) => joint,
+        }
+    }
+}
+
+impl From<DistanceJoint> for Joint {
+    fn from(joint: DistanceJoint) -> Self {
+        Joint::Distance(joint)
+    }
+}
+
+impl From<RevoluteJoint> for Joint {
+    fn from(joint: RevoluteJoint) -> Self {
+        Joint::Revolute(joint)
+    }
+}
+
+impl From<PrismaticJoint> for Joint {
+    fn from(joint: PrismaticJoint) -> Self {
+        Joint::Prismatic(joint)
+    }
+}
+
+impl From<WeldJoint> for Joint {
+    fn from(joint: WeldJoint) -> Self {
+        Joint::Weld(joint)
+    }
+}
+
+impl From<WheelJoint> for Joint {
+    fn from(joint: WheelJoint) -> Self {
+        Joint::Wheel(joint)
+    }
+}
+
+impl From<MouseJoint> for Joint {
+    fn from(joint: MouseJoint) -> Self {
+        Joint::Mouse(joint)
+    }
+}
+
+/// Solver side of a joint, same phases as the contact solver
+pub(crate) trait JointConstraint {
+    fn bodies(&self) -> (BodyHandle, BodyHandle);
+
+    /// Whether the connected bodies still collide with each other
+    fn collide_connected(&self) -> bool;
+
+    fn local_anchors(&self) -> (Vector2D, Vector2D);
+
+    /// Computes the per step lever arms and effective masses.
+    /// Drops the impulses accumulated last step unless warm starting.
+    fn prepare(&mut self, body_a: &RigidBody, body_b: &RigidBody, dt: f32, warm_starting: bool);
+
+    /// Re-applies the impulses accumulated last step
+    fn warm_start(&self, state: &mut VelocityState);
+
+    fn solve_velocity(&mut self, state: &mut VelocityState, dt: f32);
+
+    /// One pass of position correction, returns true once the joint error is within the slop
+    fn solve_position(&self, state: &mut PositionState) -> bool;
+}
+
+/// Angle error (radians) a joint limit tolerates before position correction kicks in, 2 degrees
+const ANGULAR_SLOP: f32 = 2.0 / 180.0 * std::f32::consts::PI;
+/// Largest angle correction applied in one position iteration, 8 degrees
+const MAX_ANGULAR_CORRECTION: f32 = 8.0 / 180.0 * std::f32::consts::PI;
+
+/// Effective mass matrix `K` of a point to point constraint with lever arms `r_a` and `r_b`
+fn point_mass_matrix(r_a: Vector2D, r_b: Vector2D, inv_mass_a: f32, inv_mass_b: f32,
+                     inv_i_a: f32, inv_i_b: f32) -> Mat22 {
+    let masses = inv_mass_a + inv_mass_b;
+    let k12 = -inv_i_a * r_a.x * r_a.y - inv_i_b * r_b.x * r_b.y;
+    [
+        [masses + inv_i_a * r_a.y * r_a.y + inv_i_b * r_b.y * r_b.y, k12],
+        [k12, masses + inv_i_a * r_a.x * r_a.x + inv_i_b * r_b.x * r_b.x],
+    ]
+}
+
+/// Solves `k * x = b`, zero for a singular `k` (both bodies static)
+fn solve_2x2(k: Mat22, b: Vector2D) -> Vector2D {
+    let determinant = k[0][0] * k[1][1] - k[0][1] * k[1][0];
+    if determinant == 0.0 {
+        return Vector2D::zero();
+    }
+    let inv_det = 1.0 / determinant;
+    Vector2D::new(
+        inv_det * (k[1][1] * b.x - k[0][1] * b.y),
+        inv_det * (k[0][0] * b.y - k[1][0] * b.x),
+    )
+}
+
+/// Row major 3x3 matrix, for joints that couple a point and an angle constraint
+type Mat33 = [[f32; 3]; 3];
+
+/// Solves `k * x = b` by Cramer's rule, zero for a singular `k`
+fn solve_3x3(k: Mat33, b: [f32; 3]) -> [f32; 3] {
+    let column = |i: usize| [k[0][i], k[1][i], k[2][i]];
+    let determinant_of = |c0: [f32; 3], c1: [f32; 3], c2: [f32; 3]| {
+        c0[0] * (c1[1] * c2[2] - c1[2] * c2[1])
+            - c1[0] * (c0[1] * c2[2] - c0[2] * c2[1])
+            + c2[0] * (c0[1] * c1[2] - c0[2] * c1[1])
+    };
+    let (c0, c1, c2) = (column(0), column(1), column(2));
+    let determinant = determinant_of(c0, c1, c2);
+    if determinant == 0.0 {
+        return [0.0; 3];
+    }
+    let inv_det = 1.0 / determinant;
+    [
+        inv_det * determinant_of(b, c1, c2),
+        inv_det * determinant_of(c0, b, c2),
+        inv_det * determinant_of(c0, c1, b),
+    ]
+}
+
+/// Implicit spring coefficients for a constraint with effective mass `mass`,
+/// tuned by its natural `frequency` (Hz) and `damping_ratio`.
+/// Returns (gamma, bias factor): the impulse softness and the fraction of the error
+/// (per second) fed back as velocity bias.
+fn soft_constraint(mass: f32, frequency: f32, damping_ratio: f32, dt: f32) -> (f32, f32) {
+    let omega = 2.0 * std::f32::consts::PI * frequency;
+    let stiffness = mass * omega * omega;
+    let damping = 2.0 * mass * damping_ratio * omega;
+    let gamma = dt * (damping + dt * stiffness);
+    if gamma > 0.0 {
+        (1.0 / gamma, dt * stiffness / gamma)
+    } else {
+        (0.0, 0.0)
+    }
+}
+
+/// Lever arm of a body local anchor in world orientation
+fn lever_arm(body: &RigidBody, local_anchor: Vector2D) -> Vector2D {
+    local_anchor.rotate(body.angle)
+}
@@ -0,0 +1,118 @@
+use crate::handle::BodyHandle;
+use crate::rigidbody::RigidBody;
+use crate::solver::{Mat22, PositionState, VelocityState};
+use crate::vector2d::Vector2D;
+
+use super::{lever_arm, point_mass_matrix, soft_constraint, solve_2x2, JointConstraint};
+
+/// Soft spring pulling an anchor on body B towards a world space `target`, for dragging bodies around.
+/// The pull is limited to `max_force`, so a dragged body still collides and can get stuck.
+/// Body A only anchors the joint and is never moved by it, use a static body such as the ground.
+#[derive(Debug, Clone)]
+pub struct MouseJoint {
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    /// Grabbed point relative to B's center, in B's frame
+    pub local_anchor_b: Vector2D,
+    /// World space point the anchor is pulled towards
+    pub target: Vector2D,
+    pub max_force: f32,
+    /// Natural frequency (Hz) of the pull
+    pub frequency: f32,
+    /// Damping of the pull, 1 is critically damped
+    pub damping_ratio: f32,
+
+    // Solver state
+    impulse: Vector2D,
+    r_b: Vector2D,
+    k: Mat22,
+    gamma: f32,
+    bias: Vector2D,
+}
+
+impl MouseJoint {
+    /// Grabs `body_b` at the world space point `target`
+    pub fn new(body_a: BodyHandle, handle_b: BodyHandle, body_b: &RigidBody, target: Vector2D, max_force: f32) -> Self {
+        Self {
+            body_a,
+            body_b: handle_b,
+            local_anchor_b: body_b.local_point(target),
+            target,
+            max_force,
+            frequency: 5.0,
+            damping_ratio: 0.7,
+            impulse: Vector2D::zero(),
+            r_b: Vector2D::zero(),
+            k: [[0.0; 2]; 2],
+            gamma: 0.0,
+            bias: Vector2D::zero(),
+        }
+    }
+
+    /// Sets the pull's `frequency` (Hz) and `damping_ratio`
+    pub fn with_spring(mut self, frequency: f32, damping_ratio: f32) -> Self {
+        self.frequency = frequency.max(0.0);
+        self.damping_ratio = damping_ratio.max(0.0);
+        self
+    }
+}
+
+impl JointConstraint for MouseJoint {
+    fn bodies(&self) -> (BodyHandle, BodyHandle) {
+        (self.body_a, self.body_b)
+    }
+
+    // The dragged body must still collide with the ground it's anchored to
+    fn collide_connected(&self) -> bool {
+        true
+    }
+
+    fn local_anchors(&self) -> (Vector2D, Vector2D) {
+        (self.target, self.local_anchor_b)
+    }
+
+    fn prepare(&mut self, _body_a: &RigidBody, body_b: &RigidBody, dt: f32, warm_starting: bool) {
+        self.r_b = lever_arm(body_b, self.local_anchor_b);
+        let (m_b, i_b) = (body_b.inv_mass, body_b.inv_moment_of_inertia);
+
+        let mass = if m_b > 0.0 { 1.0 / m_b } else { 0.0 };
+        let (gamma, bias_factor) = soft_constraint(mass, self.frequency, self.damping_ratio, dt);
+        self.gamma = gamma;
+        self.bias = (body_b.position + self.r_b - self.target) * bias_factor;
+
+        self.k = point_mass_matrix(Vector2D::zero(), self.r_b, 0.0, m_b, 0.0, i_b);
+        self.k[0][0] += gamma;
+        self.k[1][1] += gamma;
+
+        if !warm_starting {
+            self.impulse = Vector2D::zero();
+        }
+    }
+
+    fn warm_start(&self, state: &mut VelocityState) {
+        state.velocity_b += self.impulse * state.inv_mass_b;
+        state.angular_velocity_b += state.inv_i_b * self.r_b.cross(&self.impulse);
+    }
+
+    fn solve_velocity(&mut self, state: &mut VelocityState, dt: f32) {
+        let velocity = state.velocity_b
+            + Vector2D::new(-state.angular_velocity_b * self.r_b.y, state.angular_velocity_b * self.r_b.x);
+        let impulse = solve_2x2(self.k, (velocity + self.bias + self.impulse * self.gamma) * -1.0);
+
+        let old_impulse = self.impulse;
+        self.impulse += impulse;
+        let max_impulse = self.max_force * dt;
+        if self.impulse.magnitude_squared() > max_impulse * max_impulse {
+            self.impulse = self.impulse.normalize() * max_impulse;
+        }
+        let impulse = self.impulse - old_impulse;
+
+        state.velocity_b += impulse * state.inv_mass_b;
+        state.angular_velocity_b += state.inv_i_b * self.r_b.cross(&impulse);
+    }
+
+    // Entirely soft, nothing to correct
+    fn solve_position(&self, _state: &mut PositionState) -> bool {
+        true
+    }
+}
@@ -0,0 +1,302 @@
+use crate::handle::BodyHandle;
+use crate::rigidbody::RigidBody;
+use crate::solver::{Mat22, PositionState, VelocityState, LINEAR_SLOP};
+use crate::vector2d::Vector2D;
+
+use super::{lever_arm, solve_2x2, solve_3x3, JointConstraint, ANGULAR_SLOP};
+
+/// Lets body B slide along an axis fixed in body A without rotating relative to it (a slider).
+/// The translation can be limited to a range and driven by a motor.
+#[derive(Debug, Clone)]
+pub struct PrismaticJoint {
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    /// Anchor points relative to each body's center, in the body's frame
+    pub local_anchor_a: Vector2D,
+    pub local_anchor_b: Vector2D,
+    /// Unit slide direction in body A's frame
+    pub local_axis_a: Vector2D,
+    /// Angle of B relative to A that is held
+    pub reference_angle: f32,
+    pub enable_limit: bool,
+    /// Translation range (pixels along the axis) while the limit is enabled
+    pub lower_translation: f32,
+    pub upper_translation: f32,
+    pub enable_motor: bool,
+    /// Speed (px/s along the axis) the motor drives towards
+    pub motor_speed: f32,
+    /// Most force the motor can apply to reach its speed
+    pub max_motor_force: f32,
+    pub collide_connected: bool,
+
+    // Solver state
+    // (perpendicular, angular) impulse of the slide constraint
+    impulse: Vector2D,
+    motor_impulse: f32,
+    lower_impulse: f32,
+    upper_impulse: f32,
+    r_a: Vector2D,
+    r_b: Vector2D,
+    axis: Vector2D,
+    perpendicular: Vector2D,
+    // Angular parts of the axis and the perpendicular: (on A, on B)
+    a1: f32,
+    a2: f32,
+    s1: f32,
+    s2: f32,
+    k: Mat22,
+    axial_mass: f32,
+    translation: f32,
+}
+
+impl PrismaticJoint {
+    pub fn new(body_a: BodyHandle, body_b: BodyHandle,
+               local_anchor_a: Vector2D, local_anchor_b: Vector2D, local_axis_a: Vector2D) -> Self {
+        Self {
+            body_a,
+            body_b,
+            local_anchor_a,
+            local_anchor_b,
+            local_axis_a: local_axis_a.normalize(),
+            reference_angle: 0.0,
+            enable_limit: false,
+            lower_translation: 0.0,
+            upper_translation: 0.0,
+            enable_motor: false,
+            motor_speed: 0.0,
+            max_motor_force: 0.0,
+            collide_connected: false,
+            impulse: Vector2D::zero(),
+            motor_impulse: 0.0,
+            lower_impulse: 0.0,
+            upper_impulse: 0.0,
+            r_a: Vector2D::zero(),
+            r_b: Vector2D::zero(),
+            axis: Vector2D::zero(),
+            perpendicular: Vector2D::zero(),
+            a1: 0.0,
+            a2: 0.0,
+            s1: 0.0,
+            s2: 0.0,
+            k: [[0.0; 2]; 2],
+            axial_mass: 0.0,
+            translation: 0.0,
+        }
+    }
+
+    /// Slider through a world space point along a world space axis, in the bodies' current pose
+    pub fn at_world_anchor(handle_a: BodyHandle, body_a: &RigidBody, handle_b: BodyHandle, body_b: &RigidBody,
+                           anchor: Vector2D, axis: Vector2D) -> Self {
+        let mut joint = Self::new(handle_a, handle_b, body_a.local_point(anchor), body_b.local_point(anchor),
+                                  axis.rotate(-body_a.angle));
+        joint.reference_angle = body_b.angle - body_a.angle;
+        joint
+    }
+
+    /// Limits the translation along the axis to `lower..=upper` (pixels)
+    pub fn with_limits(mut self, lower: f32, upper: f32) -> Self {
+        self.enable_limit = true;
+        self.lower_translation = lower.min(upper);
+        self.upper_translation = lower.max(upper);
+        self
+    }
+
+    /// Drives the slider at `speed` (px/s) with at most `max_force`
+    pub fn with_motor(mut self, speed: f32, max_force: f32) -> Self {
+        self.enable_motor = true;
+        self.motor_speed = speed;
+        self.max_motor_force = max_force;
+        self
+    }
+
+    /// Current offset of the anchor on B from the anchor on A, along the axis
+    pub fn joint_translation(&self, body_a: &RigidBody, body_b: &RigidBody) -> f32 {
+        let offset = body_b.world_point(self.local_anchor_b) - body_a.world_point(self.local_anchor_a);
+        offset.dot(&self.local_axis_a.rotate(body_a.angle))
+    }
+
+    /// Force the motor applied last step
+    pub fn motor_force(&self, dt: f32) -> f32 {
+        self.motor_impulse / dt
+    }
+
+    /// Applies an impulse along the axis, with its angular parts
+    fn apply_axial(&self, state: &mut VelocityState, impulse: f32) {
+        state.apply_parts(self.axis * impulse, impulse * self.a1, impulse * self.a2);
+    }
+}
+
+impl JointConstraint for PrismaticJoint {
+    fn bodies(&self) -> (BodyHandle, BodyHandle) {
+        (self.body_a, self.body_b)
+    }
+
+    fn collide_connected(&self) -> bool {
+        self.collide_connected
+    }
+
+    fn local_anchors(&self) -> (Vector2D, Vector2D) {
+        (self.local_anchor_a, self.local_anchor_b)
+    }
+
+    fn prepare(&mut self, body_a: &RigidBody, body_b: &RigidBody, _dt: f32, warm_starting: bool) {
+        self.r_a = lever_arm(body_a, self.local_anchor_a);
+        self.r_b = lever_arm(body_b, self.local_anchor_b);
+        let offset = (body_b.position + self.r_b) - (body_a.position + self.r_a);
+        let (m_a, m_b) = (body_a.inv_mass, body_b.inv_mass);
+        let (i_a, i_b) = (body_a.inv_moment_of_inertia, body_b.inv_moment_of_inertia);
+
+        // Motor and limits act along the axis
+        self.axis = self.local_axis_a.rotate(body_a.angle);
+        self.a1 = (offset + self.r_a).cross(&self.axis);
+        self.a2 = self.r_b.cross(&self.axis);
+        let axial = m_a + m_b + i_a * self.a1 * self.a1 + i_b * self.a2 * self.a2;
+        self.axial_mass = if axial > 0.0 { 1.0 / axial } else { 0.0 };
+        self.translation = offset.dot(&self.axis);
+
+        // The slide constraint blocks motion across the axis and relative rotation
+        self.perpendicular = self.axis.perpendicular();
+        self.s1 = (offset + self.r_a).cross(&self.perpendicular);
+        self.s2 = self.r_b.cross(&self.perpendicular);
+        let k11 = m_a + m_b + i_a * self.s1 * self.s1 + i_b * self.s2 * self.s2;
+        let k12 = i_a * self.s1 + i_b * self.s2;
+        // Both bodies with fixed rotation, keep K invertible
+        let k22 = if i_a + i_b == 0.0 { 1.0 } else { i_a + i_b };
+        self.k = [[k11, k12], [k12, k22]];
+
+        if !self.enable_limit {
+            self.lower_impulse = 0.0;
+            self.upper_impulse = 0.0;
+        }
+        if !self.enable_motor {
+            self.motor_impulse = 0.0;
+        }
+        if !warm_starting {
+            self.impulse = Vector2D::zero();
+            self.motor_impulse = 0.0;
+            self.lower_impulse = 0.0;
+            self.upper_impulse = 0.0;
+        }
+    }
+
+    fn warm_start(&self, state: &mut VelocityState) {
+        let axial = self.motor_impulse + self.lower_impulse - self.upper_impulse;
+        let impulse = self.perpendicular * self.impulse.x + self.axis * axial;
+        let angular_a = self.impulse.x * self.s1 + self.impulse.y + axial * self.a1;
+        let angular_b = self.impulse.x * self.s2 + self.impulse.y + axial * self.a2;
+        state.apply_parts(impulse, angular_a, angular_b);
+    }
+
+    fn solve_velocity(&mut self, state: &mut VelocityState, dt: f32) {
+        let axial_speed = |state: &VelocityState| {
+            self.axis.dot(&(state.velocity_b - state.velocity_a))
+                + self.a2 * state.angular_velocity_b - self.a1 * state.angular_velocity_a
+        };
+
+        if self.enable_motor {
+            let impulse = self.axial_mass * (self.motor_speed - axial_speed(state));
+            let max_impulse = self.max_motor_force * dt;
+            let new_impulse = (self.motor_impulse + impulse).clamp(-max_impulse, max_impulse);
+            let impulse = new_impulse - self.motor_impulse;
+            self.motor_impulse = new_impulse;
+            self.apply_axial(state, impulse);
+        }
+
+        if self.enable_limit {
+            // Lower limit, may only push B further along the axis.
+            // While above the limit the bias lets it close the gap within this step.
+            {
+                let gap = (self.translation - self.lower_translation).max(0.0);
+                let impulse = -self.axial_mass * (axial_speed(state) + gap / dt);
+                let new_impulse = (self.lower_impulse + impulse).max(0.0);
+                let impulse = new_impulse - self.lower_impulse;
+                self.lower_impulse = new_impulse;
+                self.apply_axial(state, impulse);
+            }
+
+            // Upper limit, may only pull B back
+            {
+                let gap = (self.upper_translation - self.translation).max(0.0);
+                let impulse = -self.axial_mass * (-axial_speed(state) + gap / dt);
+                let new_impulse = (self.upper_impulse + impulse).max(0.0);
+                let impulse = new_impulse - self.upper_impulse;
+                self.upper_impulse = new_impulse;
+                self.apply_axial(state, -impulse);
+            }
+        }
+
+        // Slide constraint, no motion across the axis and no relative rotation
+        let speed = Vector2D::new(
+            self.perpendicular.dot(&(state.velocity_b - state.velocity_a))
+                + self.s2 * state.angular_velocity_b - self.s1 * state.angular_velocity_a,
+            state.angular_velocity_b - state.angular_velocity_a,
+        );
+        let impulse = solve_2x2(self.k, speed * -1.0);
+        self.impulse += impulse;
+        state.apply_parts(
+            self.perpendicular * impulse.x,
+            impulse.x * self.s1 + impulse.y,
+            impulse.x * self.s2 + impulse.y,
+        );
+    }
+
+    fn solve_position(&self, state: &mut PositionState) -> bool {
+        let (r_a, r_b, offset) = state.anchor_offsets(self.local_anchor_a, self.local_anchor_b);
+        let (m_a, m_b, i_a, i_b) = (state.inv_mass_a, state.inv_mass_b, state.inv_i_a, state.inv_i_b);
+
+        let axis = self.local_axis_a.rotate(state.angle_a);
+        let a1 = (offset + r_a).cross(&axis);
+        let a2 = r_b.cross(&axis);
+        let perpendicular = axis.perpendicular();
+        let s1 = (offset + r_a).cross(&perpendicular);
+        let s2 = r_b.cross(&perpendicular);
+
+        let perpendicular_error = perpendicular.dot(&offset);
+        let angular_error = state.angle_b - state.angle_a - self.reference_angle;
+        let mut linear_error = perpendicular_error.abs();
+
+        // Translation past a limit, corrected together with the slide constraint
+        let mut limit_error = None;
+        if self.enable_limit {
+            let translation = axis.dot(&offset);
+            if self.upper_translation - self.lower_translation < 2.0 * LINEAR_SLOP {
+                limit_error = Some(translation - self.lower_translation);
+            } else if translation <= self.lower_translation {
+                limit_error = Some((translation - self.lower_translation).min(0.0));
+            } else if translation >= self.upper_translation {
+                limit_error = Some((translation - self.upper_translation).max(0.0));
+            }
+            if let Some(error) = limit_error {
+                linear_error = linear_error.max(error.abs());
+            }
+        }
+
+        let k11 = m_a + m_b + i_a * s1 * s1 + i_b * s2 * s2;
+        let k12 = i_a * s1 + i_b * s2;
+        let k22 = if i_a + i_b == 0.0 { 1.0 } else { i_a + i_b };
+
+        let [perpendicular_impulse, angular_impulse, axial_impulse] = match limit_error {
+            Some(error) => {
+                let k13 = i_a * s1 * a1 + i_b * s2 * a2;
+                let k23 = i_a * a1 + i_b * a2;
+                let k33 = m_a + m_b + i_a * a1 * a1 + i_b * a2 * a2;
+                solve_3x3(
+                    [[k11, k12, k13], [k12, k22, k23], [k13, k23, k33]],
+                    [-perpendicular_error, -angular_error, -error],
+                )
+            }
+            None => {
+                let impulse = solve_2x2([[k11, k12], [k12, k22]], Vector2D::new(-perpendicular_error, -angular_error));
+                [impulse.x, impulse.y, 0.0]
+            }
+        };
+
+        state.apply_parts(
+            perpendicular * perpendicular_impulse + axis * axial_impulse,
+            perpendicular_impulse * s1 + angular_impulse + axial_impulse * a1,
+            perpendicular_impulse * s2 + angular_impulse + axial_impulse * a2,
+        );
+
+        linear_error <= LINEAR_SLOP && angular_error.abs() <= ANGULAR_SLOP
+    }
+}
@@ -0,0 +1,229 @@
+use crate::handle::BodyHandle;
+use crate::rigidbody::RigidBody;
+use crate::solver::{Mat22, PositionState, VelocityState, LINEAR_SLOP, MAX_LINEAR_CORRECTION};
+use crate::vector2d::Vector2D;
+
+use super::{lever_arm, point_mass_matrix, solve_2x2, JointConstraint, ANGULAR_SLOP, MAX_ANGULAR_CORRECTION};
+
+/// Pins two bodies together at a shared anchor and lets them rotate relative to each other (a hinge).
+/// The relative rotation can be limited to a range and driven by a motor.
+#[derive(Debug, Clone)]
+pub struct RevoluteJoint {
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    /// Anchor points relative to each body's center, in the body's frame
+    pub local_anchor_a: Vector2D,
+    pub local_anchor_b: Vector2D,
+    /// Angle of B relative to A that counts as a joint angle of 0
+    pub reference_angle: f32,
+    pub enable_limit: bool,
+    /// Joint angle range (radians) while the limit is enabled
+    pub lower_angle: f32,
+    pub upper_angle: f32,
+    pub enable_motor: bool,
+    /// Relative angular velocity (rad/s) the motor drives towards
+    pub motor_speed: f32,
+    /// Most torque the motor can apply to reach its speed
+    pub max_motor_torque: f32,
+    pub collide_connected: bool,
+
+    // Solver state
+    impulse: Vector2D,
+    motor_impulse: f32,
+    lower_impulse: f32,
+    upper_impulse: f32,
+    r_a: Vector2D,
+    r_b: Vector2D,
+    mass: Mat22,
+    axial_mass: f32,
+    angle: f32,
+}
+
+impl RevoluteJoint {
+    pub fn new(body_a: BodyHandle, body_b: BodyHandle,
+               local_anchor_a: Vector2D, local_anchor_b: Vector2D) -> Self {
+        Self {
+            body_a,
+            body_b,
+            local_anchor_a,
+            local_anchor_b,
+            reference_angle: 0.0,
+            enable_limit: false,
+            lower_angle: 0.0,
+            upper_angle: 0.0,
+            enable_motor: false,
+            motor_speed: 0.0,
+            max_motor_torque: 0.0,
+            collide_connected: false,
+            impulse: Vector2D::zero(),
+            motor_impulse: 0.0,
+            lower_impulse: 0.0,
+            upper_impulse: 0.0,
+            r_a: Vector2D::zero(),
+            r_b: Vector2D::zero(),
+            mass: [[0.0; 2]; 2],
+            axial_mass: 0.0,
+            angle: 0.0,
+        }
+    }
+
+    /// Hinge at a world space point, the current relative angle of the bodies becomes angle 0
+    pub fn at_world_anchor(handle_a: BodyHandle, body_a: &RigidBody,
+                           handle_b: BodyHandle, body_b: &RigidBody, anchor: Vector2D) -> Self {
+        let mut joint = Self::new(handle_a, handle_b, body_a.local_point(anchor), body_b.local_point(anchor));
+        joint.reference_angle = body_b.angle - body_a.angle;
+        joint
+    }
+
+    /// Limits the joint angle to `lower..=upper` (radians)
+    pub fn with_limits(mut self, lower: f32, upper: f32) -> Self {
+        self.enable_limit = true;
+        self.lower_angle = lower.min(upper);
+        self.upper_angle = lower.max(upper);
+        self
+    }
+
+    /// Drives the joint at `speed` (rad/s) with at most `max_torque`
+    pub fn with_motor(mut self, speed: f32, max_torque: f32) -> Self {
+        self.enable_motor = true;
+        self.motor_speed = speed;
+        self.max_motor_torque = max_torque;
+        self
+    }
+
+    /// Current angle of B relative to A, minus the reference angle
+    pub fn joint_angle(&self, body_a: &RigidBody, body_b: &RigidBody) -> f32 {
+        body_b.angle - body_a.angle - self.reference_angle
+    }
+
+    /// Torque the motor applied last step
+    pub fn motor_torque(&self, dt: f32) -> f32 {
+        self.motor_impulse / dt
+    }
+}
+
+impl JointConstraint for RevoluteJoint {
+    fn bodies(&self) -> (BodyHandle, BodyHandle) {
+        (self.body_a, self.body_b)
+    }
+
+    fn collide_connected(&self) -> bool {
+        self.collide_connected
+    }
+
+    fn local_anchors(&self) -> (Vector2D, Vector2D) {
+        (self.local_anchor_a, self.local_anchor_b)
+    }
+
+    fn prepare(&mut self, body_a: &RigidBody, body_b: &RigidBody, _dt: f32, warm_starting: bool) {
+        self.r_a = lever_arm(body_a, self.local_anchor_a);
+        self.r_b = lever_arm(body_b, self.local_anchor_b);
+        let (i_a, i_b) = (body_a.inv_moment_of_inertia, body_b.inv_moment_of_inertia);
+
+        self.mass = point_mass_matrix(self.r_a, self.r_b, body_a.inv_mass, body_b.inv_mass, i_a, i_b);
+        self.axial_mass = if i_a + i_b > 0.0 { 1.0 / (i_a + i_b) } else { 0.0 };
+        self.angle = self.joint_angle(body_a, body_b);
+
+        if !self.enable_limit {
+            self.lower_impulse = 0.0;
+            self.upper_impulse = 0.0;
+        }
+        if !self.enable_motor {
+            self.motor_impulse = 0.0;
+        }
+        if !warm_starting {
+            self.impulse = Vector2D::zero();
+            self.motor_impulse = 0.0;
+            self.lower_impulse = 0.0;
+            self.upper_impulse = 0.0;
+        }
+    }
+
+    fn warm_start(&self, state: &mut VelocityState) {
+        state.apply(self.impulse, self.r_a, self.r_b);
+        state.apply_angular(self.motor_impulse + self.lower_impulse - self.upper_impulse);
+    }
+
+    fn solve_velocity(&mut self, state: &mut VelocityState, dt: f32) {
+        // Nothing can rotate (both bodies have infinite inertia), only the point constraint applies
+        let fixed_rotation = self.axial_mass == 0.0;
+
+        if self.enable_motor && !fixed_rotation {
+            let speed = state.angular_velocity_b - state.angular_velocity_a - self.motor_speed;
+            let impulse = -self.axial_mass * speed;
+            let max_impulse = self.max_motor_torque * dt;
+            let new_impulse = (self.motor_impulse + impulse).clamp(-max_impulse, max_impulse);
+            let impulse = new_impulse - self.motor_impulse;
+            self.motor_impulse = new_impulse;
+            state.apply_angular(impulse);
+        }
+
+        if self.enable_limit && !fixed_rotation {
+            // Lower limit, may only increase the angle.
+            // While above the limit the bias lets the angle close the gap within this step.
+            {
+                let gap = (self.angle - self.lower_angle).max(0.0);
+                let speed = state.angular_velocity_b - state.angular_velocity_a;
+                let impulse = -self.axial_mass * (speed + gap / dt);
+                let new_impulse = (self.lower_impulse + impulse).max(0.0);
+                let impulse = new_impulse - self.lower_impulse;
+                self.lower_impulse = new_impulse;
+                state.apply_angular(impulse);
+            }
+
+            // Upper limit, may only decrease the angle
+            {
+                let gap = (self.upper_angle - self.angle).max(0.0);
+                let speed = state.angular_velocity_a - state.angular_velocity_b;
+                let impulse = -self.axial_mass * (speed + gap / dt);
+                let new_impulse = (self.upper_impulse + impulse).max(0.0);
+                let impulse = new_impulse - self.upper_impulse;
+                self.upper_impulse = new_impulse;
+                state.apply_angular(-impulse);
+            }
+        }
+
+        // Keep the anchors together
+        let velocity = state.relative_velocity(self.r_a, self.r_b);
+        let impulse = solve_2x2(self.mass, velocity * -1.0);
+        self.impulse += impulse;
+        state.apply(impulse, self.r_a, self.r_b);
+    }
+
+    fn solve_position(&self, state: &mut PositionState) -> bool {
+        let mut angular_error = 0.0_f32;
+        let fixed_rotation = state.inv_i_a + state.inv_i_b == 0.0;
+
+        if self.enable_limit && !fixed_rotation {
+            let angle = state.angle_b - state.angle_a - self.reference_angle;
+            let error = if self.upper_angle - self.lower_angle < 2.0 * ANGULAR_SLOP {
+                // Limits (nearly) equal, hold the angle
+                (angle - self.lower_angle).clamp(-MAX_ANGULAR_CORRECTION, MAX_ANGULAR_CORRECTION)
+            } else if angle <= self.lower_angle {
+                (angle - self.lower_angle + ANGULAR_SLOP).clamp(-MAX_ANGULAR_CORRECTION, 0.0)
+            } else if angle >= self.upper_angle {
+                (angle - self.upper_angle - ANGULAR_SLOP).clamp(0.0, MAX_ANGULAR_CORRECTION)
+            } else {
+                0.0
+            };
+
+            let impulse = -error / (state.inv_i_a + state.inv_i_b);
+            state.apply_angular(impulse);
+            angular_error = error.abs();
+        }
+
+        // Pull the anchors back together with the lever arms at the corrected angles
+        let (r_a, r_b, separation) = state.anchor_offsets(self.local_anchor_a, self.local_anchor_b);
+        let linear_error = separation.magnitude();
+        let correction = if linear_error > MAX_LINEAR_CORRECTION {
+            separation * (MAX_LINEAR_CORRECTION / linear_error)
+        } else {
+            separation
+        };
+        let mass = point_mass_matrix(r_a, r_b, state.inv_mass_a, state.inv_mass_b, state.inv_i_a, state.inv_i_b);
+        let impulse = solve_2x2(mass, correction * -1.0);
+        state.apply(impulse, r_a, r_b);
+
+        linear_error <= LINEAR_SLOP && angular_error <= ANGULAR_SLOP
+    }
+}
@@ -0,0 +1,180 @@
+use crate::handle::BodyHandle;
+use crate::rigidbody::RigidBody;
+use crate::solver::{PositionState, VelocityState, LINEAR_SLOP};
+use crate::vector2d::Vector2D;
+
+use super::{lever_arm, point_mass_matrix, soft_constraint, solve_2x2, solve_3x3, JointConstraint, Mat33, ANGULAR_SLOP};
+
+/// Glues two bodies together at an anchor, holding both their offset and their relative angle.
+/// With a `frequency` above zero the angular part becomes a spring, so the bodies can flex.
+#[derive(Debug, Clone)]
+pub struct WeldJoint {
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    /// Anchor points relative to each body's center, in the body's frame
+    pub local_anchor_a: Vector2D,
+    pub local_anchor_b: Vector2D,
+    /// Angle of B relative to A that is held
+    pub reference_angle: f32,
+    /// Natural frequency (Hz) of the angular spring, 0 for a rigid weld
+    pub frequency: f32,
+    /// Damping of the angular spring, 1 is critically damped
+    pub damping_ratio: f32,
+    pub collide_connected: bool,
+
+    // Solver state
+    // (linear x, linear y, angular) impulse
+    impulse: [f32; 3],
+    r_a: Vector2D,
+    r_b: Vector2D,
+    k: Mat33,
+    gamma: f32,
+    bias: f32,
+    angular_mass: f32,
+}
+
+impl WeldJoint {
+    pub fn new(body_a: BodyHandle, body_b: BodyHandle,
+               local_anchor_a: Vector2D, local_anchor_b: Vector2D) -> Self {
+        Self {
+            body_a,
+            body_b,
+            local_anchor_a,
+            local_anchor_b,
+            reference_angle: 0.0,
+            frequency: 0.0,
+            damping_ratio: 0.0,
+            collide_connected: false,
+            impulse: [0.0; 3],
+            r_a: Vector2D::zero(),
+            r_b: Vector2D::zero(),
+            k: [[0.0; 3]; 3],
+            gamma: 0.0,
+            bias: 0.0,
+            angular_mass: 0.0,
+        }
+    }
+
+    /// Weld at a world space point, holding the bodies' current relative pose
+    pub fn at_world_anchor(handle_a: BodyHandle, body_a: &RigidBody,
+                           handle_b: BodyHandle, body_b: &RigidBody, anchor: Vector2D) -> Self {
+        let mut joint = Self::new(handle_a, handle_b, body_a.local_point(anchor), body_b.local_point(anchor));
+        joint.reference_angle = body_b.angle - body_a.angle;
+        joint
+    }
+
+    /// Makes the angular part a spring of `frequency` (Hz) and `damping_ratio`
+    pub fn with_softness(mut self, frequency: f32, damping_ratio: f32) -> Self {
+        self.frequency = frequency.max(0.0);
+        self.damping_ratio = damping_ratio.max(0.0);
+        self
+    }
+
+    fn is_soft(&self) -> bool {
+        self.frequency > 0.0
+    }
+}
+
+/// Effective mass matrix of the combined point and angle constraint
+fn weld_mass_matrix(r_a: Vector2D, r_b: Vector2D, inv_mass_a: f32, inv_mass_b: f32,
+                    inv_i_a: f32, inv_i_b: f32) -> Mat33 {
+    let point = point_mass_matrix(r_a, r_b, inv_mass_a, inv_mass_b, inv_i_a, inv_i_b);
+    let k13 = -inv_i_a * r_a.y - inv_i_b * r_b.y;
+    let k23 = inv_i_a * r_a.x + inv_i_b * r_b.x;
+    [
+        [point[0][0], point[0][1], k13],
+        [point[1][0], point[1][1], k23],
+        [k13, k23, inv_i_a + inv_i_b],
+    ]
+}
+
+impl JointConstraint for WeldJoint {
+    fn bodies(&self) -> (BodyHandle, BodyHandle) {
+        (self.body_a, self.body_b)
+    }
+
+    fn collide_connected(&self) -> bool {
+        self.collide_connected
+    }
+
+    fn local_anchors(&self) -> (Vector2D, Vector2D) {
+        (self.local_anchor_a, self.local_anchor_b)
+    }
+
+    fn prepare(&mut self, body_a: &RigidBody, body_b: &RigidBody, dt: f32, warm_starting: bool) {
+        self.r_a = lever_arm(body_a, self.local_anchor_a);
+        self.r_b = lever_arm(body_b, self.local_anchor_b);
+        let (i_a, i_b) = (body_a.inv_moment_of_inertia, body_b.inv_moment_of_inertia);
+        self.k = weld_mass_matrix(self.r_a, self.r_b, body_a.inv_mass, body_b.inv_mass, i_a, i_b);
+
+        self.gamma = 0.0;
+        self.bias = 0.0;
+        self.angular_mass = 0.0;
+        if self.is_soft() && i_a + i_b > 0.0 {
+            let (gamma, bias_factor) = soft_constraint(1.0 / (i_a + i_b), self.frequency, self.damping_ratio, dt);
+            let error = body_b.angle - body_a.angle - self.reference_angle;
+            self.gamma = gamma;
+            self.bias = error * bias_factor;
+            self.angular_mass = 1.0 / (i_a + i_b + gamma);
+        }
+
+        if !warm_starting {
+            self.impulse = [0.0; 3];
+        }
+    }
+
+    fn warm_start(&self, state: &mut VelocityState) {
+        state.apply(Vector2D::new(self.impulse[0], self.impulse[1]), self.r_a, self.r_b);
+        state.apply_angular(self.impulse[2]);
+    }
+
+    fn solve_velocity(&mut self, state: &mut VelocityState, _dt: f32) {
+        // A soft weld runs its angular spring first and then holds the anchors together,
+        // as does a weld between bodies that can't rotate
+        if self.is_soft() || self.k[2][2] == 0.0 {
+            let speed = state.angular_velocity_b - state.angular_velocity_a;
+            let impulse = -self.angular_mass * (speed + self.bias + self.gamma * self.impulse[2]);
+            self.impulse[2] += impulse;
+            state.apply_angular(impulse);
+
+            let velocity = state.relative_velocity(self.r_a, self.r_b);
+            let point = [[self.k[0][0], self.k[0][1]], [self.k[1][0], self.k[1][1]]];
+            let impulse = solve_2x2(point, velocity * -1.0);
+            self.impulse[0] += impulse.x;
+            self.impulse[1] += impulse.y;
+            state.apply(impulse, self.r_a, self.r_b);
+            return;
+        }
+
+        let velocity = state.relative_velocity(self.r_a, self.r_b);
+        let speed = state.angular_velocity_b - state.angular_velocity_a;
+        let impulse = solve_3x3(self.k, [-velocity.x, -velocity.y, -speed]);
+        for (total, part) in self.impulse.iter_mut().zip(impulse) {
+            *total += part;
+        }
+        state.apply(Vector2D::new(impulse[0], impulse[1]), self.r_a, self.r_b);
+        state.apply_angular(impulse[2]);
+    }
+
+    fn solve_position(&self, state: &mut PositionState) -> bool {
+        let (r_a, r_b, separation) = state.anchor_offsets(self.local_anchor_a, self.local_anchor_b);
+        let (m_a, m_b, i_a, i_b) = (state.inv_mass_a, state.inv_mass_b, state.inv_i_a, state.inv_i_b);
+        let linear_error = separation.magnitude();
+
+        // A soft weld leaves the angle to its spring
+        if self.is_soft() || i_a + i_b == 0.0 {
+            let mass = point_mass_matrix(r_a, r_b, m_a, m_b, i_a, i_b);
+            let impulse = solve_2x2(mass, separation * -1.0);
+            state.apply(impulse, r_a, r_b);
+            return linear_error <= LINEAR_SLOP;
+        }
+
+        let angular_error = state.angle_b - state.angle_a - self.reference_angle;
+        let k = weld_mass_matrix(r_a, r_b, m_a, m_b, i_a, i_b);
+        let impulse = solve_3x3(k, [-separation.x, -separation.y, -angular_error]);
+        state.apply(Vector2D::new(impulse[0], impulse[1]), r_a, r_b);
+        state.apply_angular(impulse[2]);
+
+        linear_error <= LINEAR_SLOP && angular_error.abs() <= ANGULAR_SLOP
+    }
+}
@@ -0,0 +1,221 @@
+use crate::handle::BodyHandle;
+use crate::rigidbody::RigidBody;
+use crate::solver::{PositionState, VelocityState, LINEAR_SLOP};
+use crate::vector2d::Vector2D;
+
+use super::{lever_arm, soft_constraint, JointConstraint};
+
+/// Attaches a wheel (body B) to a chassis (body A): the wheel may travel along an axis fixed
+/// in the chassis, held by a suspension spring, and rotates freely or driven by a motor.
+#[derive(Debug, Clone)]
+pub struct WheelJoint {
+    pub body_a: BodyHandle,
+    pub body_b: BodyHandle,
+    /// Anchor points relative to each body's center, in the body's frame
+    pub local_anchor_a: Vector2D,
+    pub local_anchor_b: Vector2D,
+    /// Unit suspension direction in body A's frame
+    pub local_axis_a: Vector2D,
+    /// Natural frequency (Hz) of the suspension spring, 0 lets the wheel travel freely
+    pub frequency: f32,
+    /// Damping of the suspension spring, 1 is critically damped
+    pub damping_ratio: f32,
+    pub enable_motor: bool,
+    /// Relative angular velocity (rad/s) the motor drives towards
+    pub motor_speed: f32,
+    /// Most torque the motor can apply to reach its speed
+    pub max_motor_torque: f32,
+    pub collide_connected: bool,
+
+    // Solver state
+    impulse: f32,
+    spring_impulse: f32,
+    motor_impulse: f32,
+    axis: Vector2D,
+    perpendicular: Vector2D,
+    // Angular parts of the axis and the perpendicular: (on A, on B)
+    a1: f32,
+    a2: f32,
+    s1: f32,
+    s2: f32,
+    mass: f32,
+    spring_mass: f32,
+    motor_mass: f32,
+    gamma: f32,
+    bias: f32,
+}
+
+impl WheelJoint {
+    pub fn new(body_a: BodyHandle, body_b: BodyHandle,
+               local_anchor_a: Vector2D, local_anchor_b: Vector2D, local_axis_a: Vector2D) -> Self {
+        Self {
+            body_a,
+            body_b,
+            local_anchor_a,
+            local_anchor_b,
+            local_axis_a: local_axis_a.normalize(),
+            frequency: 2.0,
+            damping_ratio: 0.7,
+            enable_motor: false,
+            motor_speed: 0.0,
+            max_motor_torque: 0.0,
+            collide_connected: false,
+            impulse: 0.0,
+            spring_impulse: 0.0,
+            motor_impulse: 0.0,
+            axis: Vector2D::zero(),
+            perpendicular: Vector2D::zero(),
+            a1: 0.0,
+            a2: 0.0,
+            s1: 0.0,
+            s2: 0.0,
+            mass: 0.0,
+            spring_mass: 0.0,
+            motor_mass: 0.0,
+            gamma: 0.0,
+            bias: 0.0,
+        }
+    }
+
+    /// Wheel whose center rests at `anchor`, with the suspension along a world space axis
+    pub fn at_world_anchor(handle_a: BodyHandle, body_a: &RigidBody, handle_b: BodyHandle, body_b: &RigidBody,
+                           anchor: Vector2D, axis: Vector2D) -> Self {
+        Self::new(handle_a, handle_b, body_a.local_point(anchor), body_b.local_point(anchor),
+                  axis.rotate(-body_a.angle))
+    }
+
+    /// Sets the suspension spring's `frequency` (Hz) and `damping_ratio`
+    pub fn with_spring(mut self, frequency: f32, damping_ratio: f32) -> Self {
+        self.frequency = frequency.max(0.0);
+        self.damping_ratio = damping_ratio.max(0.0);
+        self
+    }
+
+    /// Drives the wheel at `speed` (rad/s) with at most `max_torque`
+    pub fn with_motor(mut self, speed: f32, max_torque: f32) -> Self {
+        self.enable_motor = true;
+        self.motor_speed = speed;
+        self.max_motor_torque = max_torque;
+        self
+    }
+
+    /// Current travel of the wheel along the suspension axis, from the anchor on A
+    pub fn joint_translation(&self, body_a: &RigidBody, body_b: &RigidBody) -> f32 {
+        let offset = body_b.world_point(self.local_anchor_b) - body_a.world_point(self.local_anchor_a);
+        offset.dot(&self.local_axis_a.rotate(body_a.angle))
+    }
+
+    /// Torque the motor applied last step
+    pub fn motor_torque(&self, dt: f32) -> f32 {
+        self.motor_impulse / dt
+    }
+}
+
+impl JointConstraint for WheelJoint {
+    fn bodies(&self) -> (BodyHandle, BodyHandle) {
+        (self.body_a, self.body_b)
+    }
+
+    fn collide_connected(&self) -> bool {
+        self.collide_connected
+    }
+
+    fn local_anchors(&self) -> (Vector2D, Vector2D) {
+        (self.local_anchor_a, self.local_anchor_b)
+    }
+
+    fn prepare(&mut self, body_a: &RigidBody, body_b: &RigidBody, dt: f32, warm_starting: bool) {
+        let r_a = lever_arm(body_a, self.local_anchor_a);
+        let r_b = lever_arm(body_b, self.local_anchor_b);
+        let offset = (body_b.position + r_b) - (body_a.position + r_a);
+        let (m_a, m_b) = (body_a.inv_mass, body_b.inv_mass);
+        let (i_a, i_b) = (body_a.inv_moment_of_inertia, body_b.inv_moment_of_inertia);
+
+        // Point to line constraint, keeps the wheel on the suspension axis
+        self.perpendicular = self.local_axis_a.rotate(body_a.angle).perpendicular();
+        self.s1 = (offset + r_a).cross(&self.perpendicular);
+        self.s2 = r_b.cross(&self.perpendicular);
+        let inv_mass = m_a + m_b + i_a * self.s1 * self.s1 + i_b * self.s2 * self.s2;
+        self.mass = if inv_mass > 0.0 { 1.0 / inv_mass } else { 0.0 };
+
+        // Suspension spring along the axis
+        self.axis = self.local_axis_a.rotate(body_a.angle);
+        self.a1 = (offset + r_a).cross(&self.axis);
+        self.a2 = r_b.cross(&self.axis);
+        let inv_mass = m_a + m_b + i_a * self.a1 * self.a1 + i_b * self.a2 * self.a2;
+        self.spring_mass = 0.0;
+        self.gamma = 0.0;
+        self.bias = 0.0;
+        if self.frequency > 0.0 && inv_mass > 0.0 {
+            let (gamma, bias_factor) = soft_constraint(1.0 / inv_mass, self.frequency, self.damping_ratio, dt);
+            self.gamma = gamma;
+            self.bias = offset.dot(&self.axis) * bias_factor;
+            self.spring_mass = 1.0 / (inv_mass + gamma);
+        } else {
+            self.spring_impulse = 0.0;
+        }
+
+        self.motor_mass = if i_a + i_b > 0.0 { 1.0 / (i_a + i_b) } else { 0.0 };
+        if !self.enable_motor {
+            self.motor_impulse = 0.0;
+        }
+        if !warm_starting {
+            self.impulse = 0.0;
+            self.spring_impulse = 0.0;
+            self.motor_impulse = 0.0;
+        }
+    }
+
+    fn warm_start(&self, state: &mut VelocityState) {
+        let impulse = self.perpendicular * self.impulse + self.axis * self.spring_impulse;
+        let angular_a = self.impulse * self.s1 + self.spring_impulse * self.a1 + self.motor_impulse;
+        let angular_b = self.impulse * self.s2 + self.spring_impulse * self.a2 + self.motor_impulse;
+        state.apply_parts(impulse, angular_a, angular_b);
+    }
+
+    fn solve_velocity(&mut self, state: &mut VelocityState, dt: f32) {
+        // Suspension spring
+        {
+            let speed = self.axis.dot(&(state.velocity_b - state.velocity_a))
+                + self.a2 * state.angular_velocity_b - self.a1 * state.angular_velocity_a;
+            let impulse = -self.spring_mass * (speed + self.bias + self.gamma * self.spring_impulse);
+            self.spring_impulse += impulse;
+            state.apply_parts(self.axis * impulse, impulse * self.a1, impulse * self.a2);
+        }
+
+        if self.enable_motor {
+            let speed = state.angular_velocity_b - state.angular_velocity_a - self.motor_speed;
+            let impulse = -self.motor_mass * speed;
+            let max_impulse = self.max_motor_torque * dt;
+            let new_impulse = (self.motor_impulse + impulse).clamp(-max_impulse, max_impulse);
+            let impulse = new_impulse - self.motor_impulse;
+            self.motor_impulse = new_impulse;
+            state.apply_angular(impulse);
+        }
+
+        // Keep the wheel on the axis
+        let speed = self.perpendicular.dot(&(state.velocity_b - state.velocity_a))
+            + self.s2 * state.angular_velocity_b - self.s1 * state.angular_velocity_a;
+        let impulse = -self.mass * speed;
+        self.impulse += impulse;
+        state.apply_parts(self.perpendicular * impulse, impulse * self.s1, impulse * self.s2);
+    }
+
+    fn solve_position(&self, state: &mut PositionState) -> bool {
+        // Only the drift off the axis is corrected, travel along it is up to the spring
+        let (r_a, r_b, offset) = state.anchor_offsets(self.local_anchor_a, self.local_anchor_b);
+        let perpendicular = self.local_axis_a.rotate(state.angle_a).perpendicular();
+        let s1 = (offset + r_a).cross(&perpendicular);
+        let s2 = r_b.cross(&perpendicular);
+
+        let error = perpendicular.dot(&offset);
+        let inv_mass = state.inv_mass_a + state.inv_mass_b
+            + state.inv_i_a * s1 * s1 + state.inv_i_b * s2 * s2;
+        if inv_mass > 0.0 {
+            let impulse = -error / inv_mass;
+            state.apply_parts(perpendicular * impulse, impulse * s1, impulse * s2);
+        }
+
+        error.abs() <= LINEAR_SLOP
+    }
+}
@@ -1,12 +1,35 @@
 pub mod vector2d;
+pub mod aabb;
 pub mod shape;
 pub mod rigidbody;
+pub mod handle;
 pub mod collision;
+pub mod events;
+pub mod broadphase;
+pub mod dynamic_tree;
+pub mod solver;
+mod island;
+pub mod integrator;
+pub mod joint;
+pub mod spring;
+pub mod query;
+pub mod toi;
 pub mod world;
 pub mod renderer;
 
 pub use vector2d::Vector2D;
-pub use shape::Shape;
-pub use rigidbody::RigidBody;
+pub use aabb::Aabb;
+pub use shape::{Shape, ShapeError};
+pub use rigidbody::{BodyType, RigidBody};
+pub use collision::{CollisionFilter, PairFilter};
+pub use handle::{BodyHandle, JointHandle, SpringHandle};
+pub use joint::{DistanceJoint, Joint, MouseJoint, PrismaticJoint, RevoluteJoint, WeldJoint, WheelJoint};
+pub use spring::{Spring, SpringEnd};
+pub use events::{ContactEvent, ContactEventKind, SensorEvent, SensorEventKind};
+pub use broadphase::{BroadPhase, SpatialHash, SweepAndPrune};
+pub use dynamic_tree::DynamicTree;
+pub use integrator::Integrator;
+pub use query::{RayControl, RayHit, ShapeCastHit};
+pub use toi::{Distance, Sweep, TimeOfImpact};
 pub use world::World;
 pub use renderer::Renderer;
\ No newline at end of file
@@ -1,12 +1,17 @@
 use physics_engine_2d::*;
-use minifb::{Key, Window, WindowOptions};
+use minifb::{Key, MouseButton, MouseMode, Window, WindowOptions};
 
 const WIDTH: usize = 800;
 const HEIGHT: usize = 600;
 
+// Travel of the elevator, a kinematic platform on the right
+const ELEVATOR_TOP: f32 = 250.0;
+const ELEVATOR_BOTTOM: f32 = 540.0;
+const ELEVATOR_SPEED: f32 = 80.0;
+
 fn main() {
     let mut window = Window::new(
-        "2D Physics Engine - Press SPACE to add objects, R to reset",
+        "2D Physics Engine - Drag objects with the mouse, SPACE to add objects, R to reset",
         WIDTH,
         HEIGHT,
         WindowOptions {
@@ -25,20 +30,28 @@ fn main() {
     let mut renderer = Renderer::new(WIDTH, HEIGHT);
     
     // Create boundaries
-    setup_boundaries(&mut world);
+    let mut ground = setup_boundaries(&mut world);
     
     // Add some initial objects
     setup_initial_scene(&mut world);
     
+    let mut drag = Drag::default();
     let mut frame_count = 0;
-    let dt = 1.0 / 60.0;
+    let mut last_frame = std::time::Instant::now();
     
     while window.is_open() && !window.is_key_down(Key::Escape) {
         // Handle input
-        handle_input(&window, &mut world);
+        handle_input(&window, &mut world, &mut ground);
+        handle_drag(&window, &mut world, ground, &mut drag);
+        drive_elevator(&mut world);
         
-        // Physics step
-        world.step(dt);
+        // Physics steps for the real time since the last frame
+        let now = std::time::Instant::now();
+        world.advance(now.duration_since(last_frame).as_secs_f32());
+        last_frame = now;
+        // The demo doesn't react to contacts, drop the events so they don't pile up
+        world.drain_contact_events();
+        world.drain_sensor_events();
         
         // Render
         renderer.draw_world(&world);
@@ -55,14 +68,15 @@ fn main() {
     }
 }
 
-fn setup_boundaries(world: &mut World) {
+/// Returns the ground, which also anchors the mouse joint while dragging
+fn setup_boundaries(world: &mut World) -> BodyHandle {
     // Ground
     let ground = RigidBody::new(
         Vector2D::new(WIDTH as f32 / 2.0, HEIGHT as f32 - 10.0),
         Shape::rectangle(WIDTH as f32 - 20.0, 20.0),
         0.0, // Static
     );
-    world.add_body(ground);
+    let ground = world.add_body(ground);
     
     // Left wall
     let left_wall = RigidBody::new(
@@ -87,6 +101,21 @@ fn setup_boundaries(world: &mut World) {
         0.0,
     );
     world.add_body(ceiling);
+    
+    // Ramp in the bottom left corner (polygon vertices get recentered on the centroid)
+    let ramp_shape = Shape::polygon(vec![
+        Vector2D::new(0.0, 0.0),
+        Vector2D::new(200.0, 0.0),
+        Vector2D::new(0.0, -100.0),
+    ]).expect("ramp vertices form a convex polygon");
+    let ramp = RigidBody::new(
+        Vector2D::new(20.0 + 200.0 / 3.0, HEIGHT as f32 - 20.0 - 100.0 / 3.0),
+        ramp_shape,
+        0.0,
+    );
+    world.add_body(ramp);
+
+    ground
 }
 
 fn setup_initial_scene(world: &mut World) {
@@ -117,9 +146,36 @@ fn setup_initial_scene(world: &mut World) {
         rect.restitution = rng.gen_range(0.1..0.4); // Even less bouncy
         world.add_body(rect);
     }
+
+    // Elevator, moved by its velocity alone, carries whatever lands on it
+    let mut elevator = RigidBody::new(
+        Vector2D::new(WIDTH as f32 - 100.0, ELEVATOR_BOTTOM),
+        Shape::rectangle(120.0, 15.0),
+        0.0,
+    );
+    elevator.set_body_type(BodyType::Kinematic);
+    elevator.velocity = Vector2D::new(0.0, -ELEVATOR_SPEED);
+    world.add_body(elevator);
+}
+
+/// Turns kinematic bodies around at the ends of the elevator's travel
+fn drive_elevator(world: &mut World) {
+    let turning: Vec<BodyHandle> = world.iter()
+        .filter(|(_, body)| body.is_kinematic())
+        .filter(|(_, body)| {
+            body.position.y < ELEVATOR_TOP && body.velocity.y < 0.0
+                || body.position.y > ELEVATOR_BOTTOM && body.velocity.y > 0.0
+        })
+        .map(|(handle, _)| handle)
+        .collect();
+    for handle in turning {
+        if let Some(body) = world.get_mut(handle) {
+            body.velocity.y *= -1.0;
+        }
+    }
 }
 
-fn handle_input(window: &Window, world: &mut World) {
+fn handle_input(window: &Window, world: &mut World, ground: &mut BodyHandle) {
     use rand::Rng;
     let mut rng = rand::thread_rng();
     
@@ -141,40 +197,105 @@ fn handle_input(window: &Window, world: &mut World) {
                 (Shape::rectangle(width, height), (width * height) * 0.01)
             };
             
-            let mut body = RigidBody::new(
-                Vector2D::new(safe_x, safe_y),
-                shape,
-                mass,
-            );
-            body.restitution = rng.gen_range(0.1..0.4); // Less bouncy
-            body.velocity = Vector2D::new(
-                rng.gen_range(-50.0..50.0),  // Reduced initial velocity
-                rng.gen_range(-25.0..25.0)
-            );
-            world.add_body(body);
+            // Don't spawn on top of another body
+            let position = Vector2D::new(safe_x, safe_y);
+            if world.query_shape(&shape, position, 0.0, &CollisionFilter::default()).is_empty() {
+                let mut body = RigidBody::new(
+                    position,
+                    shape,
+                    mass,
+                );
+                body.restitution = rng.gen_range(0.1..0.4); // Less bouncy
+                body.velocity = Vector2D::new(
+                    rng.gen_range(-50.0..50.0),  // Reduced initial velocity
+                    rng.gen_range(-25.0..25.0)
+                );
+                world.add_body(body);
+            }
         }
     }
     
+    // Fire a fast bullet with B, swept each step so it can't pass through the walls
+    if window.is_key_pressed(Key::B, minifb::KeyRepeat::No)
+        && let Some((mx, my)) = window.get_mouse_pos(minifb::MouseMode::Clamp) {
+        let position = Vector2D::new(mx, my);
+        let shape = Shape::circle(4.0);
+        if world.query_shape(&shape, position, 0.0, &CollisionFilter::default()).is_empty() {
+            let direction = rng.gen_range(0.0..std::f32::consts::TAU);
+            let mut bullet = RigidBody::new(position, shape, 0.5);
+            bullet.velocity = Vector2D::new(direction.cos(), direction.sin()) * 4000.0;
+            bullet.is_bullet = true;
+            world.add_body(bullet);
+        }
+    }
+
     // Reset with R
     if window.is_key_pressed(Key::R, minifb::KeyRepeat::No) {
-        world.bodies.clear();
-        setup_boundaries(world);
+        world.clear();
+        *ground = setup_boundaries(world);
         setup_initial_scene(world);
     }
 }
 
+/// Mouse joint of the body being dragged
+#[derive(Default)]
+struct Drag {
+    joint: Option<JointHandle>,
+    button_was_down: bool,
+}
+
+fn handle_drag(window: &Window, world: &mut World, ground: BodyHandle, drag: &mut Drag) {
+    let button_down = window.get_mouse_down(MouseButton::Left);
+    let pressed = button_down && !drag.button_was_down;
+    drag.button_was_down = button_down;
+
+    let Some((mx, my)) = window.get_mouse_pos(MouseMode::Clamp) else { return };
+    let mouse = Vector2D::new(mx, my);
+
+    // Pick the dynamic body under the cursor on click
+    if pressed {
+        let picked = world.query_point(mouse, &CollisionFilter::default())
+            .into_iter()
+            .find(|&handle| world.get(handle).is_some_and(|body| body.is_dynamic()));
+        if let Some(handle) = picked {
+            let body = world.get(handle).expect("picked body exists");
+            // About a hundred times the body's weight, strong but still stopped by walls
+            let max_force = body.mass * 50_000.0;
+            let joint = MouseJoint::new(ground, handle, body, mouse, max_force);
+            drag.joint = Some(world.add_joint(joint));
+        }
+    }
+
+    let Some(joint) = drag.joint else { return };
+    if !button_down {
+        // Let go, the body keeps the velocity it was dragged with
+        world.remove_joint(joint);
+        drag.joint = None;
+    } else if let Some(Joint::Mouse(mouse_joint)) = world.get_joint_mut(joint) {
+        mouse_joint.target = mouse;
+    } else {
+        // The body was removed, e.g. by a reset
+        drag.joint = None;
+    }
+}
+
 fn draw_ui(renderer: &mut Renderer, world: &World, frame: u32) {
     // Count dynamic bodies (excluding static boundaries)
-    let dynamic_count = world.bodies.iter()
-        .filter(|b| !b.is_static)
+    let dynamic_count = world.iter()
+        .filter(|(_, b)| b.is_dynamic())
+        .count();
+    let awake_count = world.iter()
+        .filter(|(_, b)| b.is_awake())
         .count();
     
     // Simple FPS counter
-    let fps_text = format!("FPS: {}", if frame % 60 == 0 { 60 } else { 0 });
-    let objects_text = format!("Objects: {}", dynamic_count);
+    let fps_text = format!("FPS: {}", if frame.is_multiple_of(60) { 60 } else { 0 });
+    let objects_text = format!("Objects: {} ({} awake)", dynamic_count, awake_count);
     
     renderer.draw_text(&fps_text, 10, 10, 0xFFFFFF);
     renderer.draw_text(&objects_text, 10, 25, 0xFFFFFF);
+    renderer.draw_text("MOUSE: Drag object", 10, HEIGHT as i32 - 70, 0xFFFFFF);
+    renderer.draw_text("B: Fire bullet", 10, HEIGHT as i32 - 55, 0xFFFFFF);
     renderer.draw_text("SPACE: Add object", 10, HEIGHT as i32 - 40, 0xFFFFFF);
     renderer.draw_text("R: Reset", 10, HEIGHT as i32 - 25, 0xFFFFFF);
 }
\ No newline at end of file
@@ -0,0 +1,37 @@
+use crate::handle::BodyHandle;
+use crate::vector2d::Vector2D;
+
+/// Where a ray hit a body
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub body: BodyHandle,
+    pub point: Vector2D,
+    /// Surface normal at the hit, facing the ray's origin
+    pub normal: Vector2D,
+    /// Distance to the hit as a fraction of the ray's length (0..=1)
+    pub fraction: f32,
+}
+
+/// What `World::raycast_with` does after reporting a hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RayControl {
+    /// Shorten the ray to end at the hit, only closer hits are reported from now on
+    Clip,
+    /// Keep the ray's length and keep reporting hits, to ignore this hit or to collect them all
+    Continue,
+    /// Report no more hits
+    Stop,
+}
+
+/// First body a swept shape runs into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeCastHit {
+    pub body: BodyHandle,
+    /// Contact point when the shape gets there
+    pub point: Vector2D,
+    /// Surface normal of the hit body, facing the cast shape.
+    /// Zero if the shape already overlaps the body at the start.
+    pub normal: Vector2D,
+    /// How far along the cast the shape gets before touching (0..=1)
+    pub fraction: f32,
+}
@@ -1,3 +1,5 @@
+use crate::joint::Joint;
+use crate::spring::SpringEnd;
 use crate::vector2d::Vector2D;
 use crate::{rigidbody::RigidBody, shape::Shape};
 use crate::world::World;
@@ -29,25 +31,83 @@ impl Renderer {
         // Clear with dark blue background
         self.clear(0x001122);
         
-        // Draw all bodies
-        for body in &world.bodies {
-            self.draw_body(body);
+        // Draw all bodies, blended between the last two steps
+        let alpha = world.alpha();
+        for (_, body) in world.iter() {
+            let (position, angle) = body.interpolated_transform(alpha);
+            self.draw_body(body, position, angle);
+        }
+
+        // Draw joints on top of the bodies they connect
+        for (_, joint) in world.joints() {
+            let (handle_a, handle_b) = joint.bodies();
+            if let (Some(body_a), Some(body_b)) = (world.get(handle_a), world.get(handle_b)) {
+                self.draw_joint(joint, body_a, body_b, alpha);
+            }
+        }
+
+        // Draw springs, a world point end stays put
+        for (_, spring) in world.springs() {
+            let (handle_a, handle_b) = spring.bodies();
+            let Some(body_a) = world.get(handle_a) else { continue };
+            let (position_a, angle_a) = body_a.interpolated_transform(alpha);
+            let anchor_a = position_a + spring.local_anchor_a.rotate(angle_a);
+            let anchor_b = match (spring.end, handle_b.and_then(|handle| world.get(handle))) {
+                (SpringEnd::Body { local_anchor, .. }, Some(body_b)) => {
+                    let (position_b, angle_b) = body_b.interpolated_transform(alpha);
+                    position_b + local_anchor.rotate(angle_b)
+                }
+                (SpringEnd::Point(point), _) => point,
+                (SpringEnd::Body { .. }, None) => continue,
+            };
+            self.draw_line(anchor_a, anchor_b, 0x66FF99);
+        }
+    }
+
+    fn draw_joint(&mut self, joint: &Joint, body_a: &RigidBody, body_b: &RigidBody, alpha: f32) {
+        let color = 0x88CCFF;
+        let (position_a, angle_a) = body_a.interpolated_transform(alpha);
+        let (position_b, angle_b) = body_b.interpolated_transform(alpha);
+        let (local_a, local_b) = joint.local_anchors();
+        let anchor_a = position_a + local_a.rotate(angle_a);
+        let anchor_b = position_b + local_b.rotate(angle_b);
+
+        match joint {
+            // A rod or rope, just the line between its ends
+            Joint::Distance(_) => self.draw_line(anchor_a, anchor_b, color),
+            // Drag line from the grabbed point to the target
+            Joint::Mouse(mouse) => self.draw_line(anchor_b, mouse.target, color),
+            // Everything else ties the anchors to their bodies
+            _ => {
+                self.draw_line(position_a, anchor_a, color);
+                self.draw_line(position_b, anchor_b, color);
+                self.draw_line(anchor_a, anchor_b, color);
+            }
         }
     }
     
-    fn draw_body(&mut self, body: &RigidBody) {
-        let color = if body.is_static {
+    fn draw_body(&mut self, body: &RigidBody, position: Vector2D, angle: f32) {
+        let color = if body.is_sensor {
+            0x2E8B57  // Green for sensors
+        } else if body.is_static() {
             0x444444  // Gray for static bodies
+        } else if body.is_kinematic() {
+            0x3A7BD5  // Blue for kinematic bodies
+        } else if !body.is_awake() {
+            0x8A4A2E  // Dim orange for sleeping bodies
         } else {
             0xFF6B35  // Orange for dynamic bodies
         };
         
         match &body.shape {
             Shape::Circle { radius } => {
-                self.draw_circle(body.position, *radius, color);
+                self.draw_circle(position, *radius, color);
             },
             Shape::Rectangle { width, height } => {
-                self.draw_rectangle(body.position, *width, *height, color);
+                self.draw_rectangle(position, angle, *width, *height, color);
+            },
+            Shape::Polygon { vertices } => {
+                self.draw_polygon(position, angle, vertices, color);
             }
         }
     }
@@ -72,18 +132,22 @@ impl Renderer {
         self.set_pixel(cx, cy, 0xFFFFFF);
     }
     
-    fn draw_rectangle(&mut self, center: Vector2D, width: f32, height: f32, color: u32) {
+    fn draw_rectangle(&mut self, center: Vector2D, angle: f32, width: f32, height: f32, color: u32) {
         let cx = center.x as i32;
         let cy = center.y as i32;
-        let w = (width / 2.0) as i32;
-        let h = (height / 2.0) as i32;
+        let half_w = width / 2.0;
+        let half_h = height / 2.0;
+        
+        // Bounding radius of the rotated rectangle
+        let r = (half_w * half_w + half_h * half_h).sqrt().ceil() as i32;
         
-        // Draw filled rectangle
-        for y in -h..=h {
-            for x in -w..=w {
-                let px = cx + x;
-                let py = cy + y;
-                self.set_pixel(px, py, color);
+        // Draw filled rectangle, testing each pixel in the rectangle's local frame
+        for y in -r..=r {
+            for x in -r..=r {
+                let local = Vector2D::new(x as f32, y as f32).rotate(-angle);
+                if local.x.abs() <= half_w && local.y.abs() <= half_h {
+                    self.set_pixel(cx + x, cy + y, color);
+                }
             }
         }
         
@@ -91,6 +155,62 @@ impl Renderer {
         self.set_pixel(cx, cy, 0xFFFFFF);
     }
     
+    fn draw_polygon(&mut self, center: Vector2D, angle: f32, vertices: &[Vector2D], color: u32) {
+        let cx = center.x as i32;
+        let cy = center.y as i32;
+        
+        // Bounding radius of the polygon around its centroid
+        let r = vertices.iter()
+            .map(|v| v.magnitude())
+            .fold(0.0, f32::max)
+            .ceil() as i32;
+        
+        // A pixel is inside a counter-clockwise convex polygon when it is left of every edge
+        for y in -r..=r {
+            for x in -r..=r {
+                let local = Vector2D::new(x as f32, y as f32).rotate(-angle);
+                let inside = (0..vertices.len()).all(|i| {
+                    let a = vertices[i];
+                    let b = vertices[(i + 1) % vertices.len()];
+                    (b - a).cross(&(local - a)) >= 0.0
+                });
+                if inside {
+                    self.set_pixel(cx + x, cy + y, color);
+                }
+            }
+        }
+        
+        // Draw center dot
+        self.set_pixel(cx, cy, 0xFFFFFF);
+    }
+    
+    fn draw_line(&mut self, from: Vector2D, to: Vector2D, color: u32) {
+        let (mut x, mut y) = (from.x as i32, from.y as i32);
+        let (x1, y1) = (to.x as i32, to.y as i32);
+
+        // Bresenham's line algorithm
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let step_x = if x < x1 { 1 } else { -1 };
+        let step_y = if y < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        loop {
+            self.set_pixel(x, y, color);
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+    
     fn set_pixel(&mut self, x: i32, y: i32, color: u32) {
         if x >= 0 && x < self.width as i32 && y >= 0 && y < self.height as i32 {
             let index = (y as usize) * self.width + (x as usize);
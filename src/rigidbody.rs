@@ -1,5 +1,19 @@
 use crate::vector2d::Vector2D;
 use crate::shape::Shape;
+use crate::aabb::Aabb;
+use crate::collision::CollisionFilter;
+
+/// How a body moves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyType {
+    /// Never moves, e.g. the ground and walls
+    Static,
+    /// Moves with the velocity it is given, unaffected by forces and collisions
+    /// but pushes dynamic bodies out of the way, e.g. moving platforms and elevators
+    Kinematic,
+    /// Moved by forces, collisions and joints
+    Dynamic,
+}
 
 #[derive(Debug, Clone)]
 pub struct RigidBody {
@@ -22,10 +36,19 @@ pub struct RigidBody {
     pub shape: Shape,
     pub restitution: f32,           // Bounciness (0-1)
     pub friction: f32,              // friction coefficient
-    pub is_static: bool,            // immovable object
+    pub(crate) body_type: BodyType,
+    pub is_sensor: bool,            // reports overlaps but never collides
+    pub is_bullet: bool,            // swept each step so it can't tunnel through thin bodies
+    pub can_sleep: bool,            // allowed to fall asleep once it comes to rest
+    pub(crate) awake: bool,
+    pub(crate) sleep_time: f32,     // seconds spent resting
+    pub filter: CollisionFilter,    // which other bodies it collides with
 
     pub force: Vector2D,            // Accumulated Forces
     pub torque: f32,                // Accumulated Torque
+
+    pub previous_position: Vector2D, // Position before the last step, for interpolation
+    pub previous_angle: f32,
 }
 
 impl RigidBody {
@@ -52,36 +75,161 @@ impl RigidBody {
             shape,
             restitution:0.8,
             friction: 0.3,
-            is_static: mass == 0.0,
+            body_type: if mass == 0.0 { BodyType::Static } else { BodyType::Dynamic },
+            is_sensor: false,
+            is_bullet: false,
+            can_sleep: true,
+            awake: true,
+            sleep_time: 0.0,
+            filter: CollisionFilter::default(),
             force: Vector2D::zero(),
             torque: 0.0,
+            previous_position: position,
+            previous_angle: 0.0,
         }
     }
+
+    /// Transform blended between the previous and the current step,
+    /// `alpha` 0 gives the previous and 1 the current (position, angle)
+    pub fn interpolated_transform(&self, alpha: f32) -> (Vector2D, f32) {
+        let position = self.previous_position + (self.position - self.previous_position) * alpha;
+        let angle = self.previous_angle + (self.angle - self.previous_angle) * alpha;
+        (position, angle)
+    }
     
+    pub fn body_type(&self) -> BodyType {
+        self.body_type
+    }
+
+    /// Changes how the body moves. Static and kinematic bodies get infinite mass so collisions
+    /// and joints can't move them, a dynamic body gets its `mass` back.
+    /// Static bodies also stop, kinematic ones keep their velocity.
+    pub fn set_body_type(&mut self, body_type: BodyType) {
+        self.body_type = body_type;
+        self.force = Vector2D::zero();
+        self.torque = 0.0;
+        if body_type == BodyType::Dynamic {
+            self.inv_mass = if self.mass == 0.0 { 0.0 } else { 1.0 / self.mass };
+            self.inv_moment_of_inertia = if self.moment_of_inertia == 0.0 { 0.0 } else { 1.0 / self.moment_of_inertia };
+        } else {
+            self.inv_mass = 0.0;
+            self.inv_moment_of_inertia = 0.0;
+        }
+        if body_type == BodyType::Static {
+            self.velocity = Vector2D::zero();
+            self.angular_velocity = 0.0;
+        } else {
+            self.set_awake(true);
+        }
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.body_type == BodyType::Static
+    }
+
+    pub fn is_kinematic(&self) -> bool {
+        self.body_type == BodyType::Kinematic
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        self.body_type == BodyType::Dynamic
+    }
+
+    /// Whether the body is simulated. Sleeping bodies are skipped until something wakes them up,
+    /// static bodies are never awake.
+    pub fn is_awake(&self) -> bool {
+        self.awake && !self.is_static()
+    }
+
+    /// Wakes the body up, or puts it to sleep and stops it.
+    /// Forces and impulses wake dynamic bodies by themselves, so do contacts with awake bodies.
+    pub fn set_awake(&mut self, awake: bool) {
+        if awake {
+            if !self.awake {
+                self.awake = true;
+                self.sleep_time = 0.0;
+            }
+        } else {
+            self.awake = false;
+            self.sleep_time = 0.0;
+            self.velocity = Vector2D::zero();
+            self.angular_velocity = 0.0;
+            self.force = Vector2D::zero();
+            self.torque = 0.0;
+        }
+    }
+
     pub fn apply_force(&mut self, force: Vector2D) {
-        if !self.is_static {
+        if self.is_dynamic() {
             self.force += force;
+            self.set_awake(true);
         }
     }
 
     pub fn apply_force_at_point(&mut self, force: Vector2D, point: Vector2D)
     {
-        if !self.is_static {
+        if self.is_dynamic() {
             self.force += force;
             let r = point - self.position;
             self.torque += r.cross(&force);
+            self.set_awake(true);
         }
     }
 
     pub fn apply_impulse(&mut self, impulse: Vector2D) {
-        if !self.is_static {
+        if self.is_dynamic() {
             self.velocity += impulse * self.inv_mass;
+            self.set_awake(true);
         }
     }
 
+    /// Impulse applied at a world space point, changes both linear and angular velocity
+    pub fn apply_impulse_at_point(&mut self, impulse: Vector2D, point: Vector2D) {
+        if self.is_dynamic() {
+            self.velocity += impulse * self.inv_mass;
+            let r = point - self.position;
+            self.angular_velocity += r.cross(&impulse) * self.inv_moment_of_inertia;
+            self.set_awake(true);
+        }
+    }
+
+    /// World space position of a point given in the body's frame
+    pub fn world_point(&self, local_point: Vector2D) -> Vector2D {
+        self.position + local_point.rotate(self.angle)
+    }
+
+    /// Point in the body's frame (relative to its center) of a world space point
+    pub fn local_point(&self, world_point: Vector2D) -> Vector2D {
+        (world_point - self.position).rotate(-self.angle)
+    }
+
+    /// Velocity of a world space point attached to the body (v + w x r)
+    pub fn velocity_at_point(&self, point: Vector2D) -> Vector2D {
+        let r = point - self.position;
+        self.velocity + Vector2D::new(-self.angular_velocity * r.y, self.angular_velocity * r.x)
+    }
+
+    /// Whether a world space point lies inside the body's shape
+    pub fn contains_point(&self, point: Vector2D) -> bool {
+        self.shape.contains_point(self.local_point(point))
+    }
+
+    /// Ray against the body's shape in world space, `direction` must be unit length.
+    /// Returns the distance to the hit and the surface normal there.
+    pub fn raycast(&self, origin: Vector2D, direction: Vector2D, max_distance: f32) -> Option<(f32, Vector2D)> {
+        self.shape.raycast(self.local_point(origin), direction.rotate(-self.angle), max_distance)
+            .map(|(distance, normal)| (distance, normal.rotate(self.angle)))
+    }
+
+    /// World space bounding box of the body's shape
+    pub fn aabb(&self) -> Aabb {
+        self.shape.aabb(self.position, self.angle)
+    }
+
     pub fn apply_angular_impulse(&mut self, impulse: f32) {
-        if !self.is_static {
+        if self.is_dynamic() {
             self.angular_velocity += impulse * self.inv_moment_of_inertia;
+            self.set_awake(true);
         }
     }
 }
\ No newline at end of file
@@ -1,12 +1,37 @@
-// use crate::vector2d::Vector2D;
+use std::fmt;
+
+use crate::aabb::Aabb;
+use crate::vector2d::Vector2D;
 
 #[derive(Debug, Clone)]
 pub enum Shape {
     // Circle { radius: f32, center: Vector2D},
     Circle { radius: f32},
     Rectangle { width: f32, height: f32},
+    /// Convex polygon, vertices in counter-clockwise order relative to the centroid
+    Polygon { vertices: Vec<Vector2D> },
+}
+
+/// Reasons a vertex list can't be turned into a `Shape::Polygon`
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShapeError {
+    TooFewVertices,
+    Degenerate,
+    NotConvex,
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShapeError::TooFewVertices => write!(f, "polygon needs at least 3 vertices"),
+            ShapeError::Degenerate => write!(f, "polygon has zero area or repeated vertices"),
+            ShapeError::NotConvex => write!(f, "polygon is not strictly convex"),
+        }
+    }
 }
 
+impl std::error::Error for ShapeError {}
+
 impl Shape {
     // pub fn circle(radius: f32, center: Vector2D) -> Self {
     //     Shape::Circle { radius, center }
@@ -21,11 +46,158 @@ impl Shape {
         Shape::Rectangle { width , height }
     }
 
+    /// Convex polygon from vertices in either winding order.
+    /// Vertices are reordered counter-clockwise and shifted so the centroid sits at the origin,
+    /// which keeps the body position at the center of mass.
+    pub fn polygon(mut vertices: Vec<Vector2D>) -> Result<Self, ShapeError> {
+        if vertices.len() < 3 {
+            return Err(ShapeError::TooFewVertices);
+        }
+
+        let signed_area = polygon_signed_area(&vertices);
+        if signed_area.abs() <= f32::EPSILON {
+            return Err(ShapeError::Degenerate);
+        }
+        if signed_area < 0.0 {
+            vertices.reverse();
+        }
+
+        let count = vertices.len();
+        let repeated = (0..count).any(|i| (vertices[(i + 1) % count] - vertices[i]).magnitude_squared() <= f32::EPSILON);
+        if repeated {
+            return Err(ShapeError::Degenerate);
+        }
+
+        // Every other vertex must lie strictly left of each edge for a convex, counter-clockwise loop
+        for i in 0..count {
+            let a = vertices[i];
+            let edge = vertices[(i + 1) % count] - a;
+            for (j, vertex) in vertices.iter().enumerate() {
+                if j == i || j == (i + 1) % count {
+                    continue;
+                }
+                if edge.cross(&(*vertex - a)) <= 0.0 {
+                    return Err(ShapeError::NotConvex);
+                }
+            }
+        }
+
+        let centroid = polygon_centroid(&vertices);
+        for v in &mut vertices {
+            *v -= centroid;
+        }
+
+        Ok(Shape::Polygon { vertices })
+    }
+
+    /// Local space vertices in counter-clockwise order, empty for circles
+    pub fn vertices(&self) -> Vec<Vector2D> {
+        match self {
+            Shape::Circle { .. } => Vec::new(),
+            Shape::Rectangle { width, height } => {
+                let half_w = width / 2.0;
+                let half_h = height / 2.0;
+                vec![
+                    Vector2D::new(-half_w, -half_h),
+                    Vector2D::new(half_w, -half_h),
+                    Vector2D::new(half_w, half_h),
+                    Vector2D::new(-half_w, half_h),
+                ]
+            }
+            Shape::Polygon { vertices } => vertices.clone(),
+        }
+    }
+
+    /// World space bounding box of the shape placed at `position` rotated by `angle`
+    pub fn aabb(&self, position: Vector2D, angle: f32) -> Aabb {
+        match self {
+            Shape::Circle { radius } => {
+                let extent = Vector2D::new(*radius, *radius);
+                Aabb::new(position - extent, position + extent)
+            }
+            _ => {
+                let world: Vec<Vector2D> = self.vertices().iter()
+                    .map(|v| position + v.rotate(angle))
+                    .collect();
+                Aabb::from_points(&world).unwrap_or(Aabb::new(position, position))
+            }
+        }
+    }
+
+    /// Whether a point in the shape's local frame lies inside (or on) the shape
+    pub fn contains_point(&self, local_point: Vector2D) -> bool {
+        match self {
+            Shape::Circle { radius } => local_point.magnitude_squared() <= radius * radius,
+            Shape::Rectangle { width, height } => {
+                local_point.x.abs() <= width / 2.0 && local_point.y.abs() <= height / 2.0
+            }
+            // Inside a counter-clockwise convex polygon means left of every edge
+            Shape::Polygon { vertices } => (0..vertices.len()).all(|i| {
+                let a = vertices[i];
+                let b = vertices[(i + 1) % vertices.len()];
+                (b - a).cross(&(local_point - a)) >= 0.0
+            }),
+        }
+    }
+
+    /// Ray against the shape in its local frame, `direction` must be unit length.
+    /// Returns the distance to the surface and the outward normal there, `None` on a miss
+    /// or when the ray starts inside the shape.
+    pub fn raycast(&self, origin: Vector2D, direction: Vector2D, max_distance: f32) -> Option<(f32, Vector2D)> {
+        match self {
+            Shape::Circle { radius } => {
+                // Solve |origin + t * direction| = radius for the nearer t
+                let b = origin.dot(&direction);
+                let c = origin.magnitude_squared() - radius * radius;
+                let discriminant = b * b - c;
+                if c <= 0.0 || b > 0.0 || discriminant < 0.0 {
+                    return None;
+                }
+                let distance = -b - discriminant.sqrt();
+                if distance > max_distance {
+                    return None;
+                }
+                Some((distance, (origin + direction * distance) / *radius))
+            }
+            _ => {
+                // Clip the ray against the half plane of every edge (counter-clockwise, outward normals)
+                let vertices = self.vertices();
+                let mut lower = 0.0_f32;
+                let mut upper = max_distance;
+                let mut hit_edge = None;
+                for i in 0..vertices.len() {
+                    let edge = vertices[(i + 1) % vertices.len()] - vertices[i];
+                    let normal = Vector2D::new(edge.y, -edge.x).normalize();
+                    let numerator = normal.dot(&(vertices[i] - origin));
+                    let denominator = normal.dot(&direction);
+                    if denominator == 0.0 {
+                        // Parallel to the edge and outside of it
+                        if numerator < 0.0 {
+                            return None;
+                        }
+                    } else if denominator < 0.0 && numerator < lower * denominator {
+                        // Entering this half plane
+                        lower = numerator / denominator;
+                        hit_edge = Some(normal);
+                    } else if denominator > 0.0 && numerator < upper * denominator {
+                        // Leaving this half plane
+                        upper = numerator / denominator;
+                    }
+                    if upper < lower {
+                        return None;
+                    }
+                }
+                hit_edge.map(|normal| (lower, normal))
+            }
+        }
+    }
+
     pub fn area(&self) -> f32 {
         match self {
             // Self::Circle { radius, center: _ } => std::f32::consts::PI * radius * radius,
             Self::Circle { radius } => std::f32::consts::PI * radius * radius,
             Self::Rectangle { width, height } => width * height,
+            Self::Polygon { vertices } => polygon_signed_area(vertices).abs(),
         }
 
     }
@@ -36,6 +208,84 @@ impl Shape {
             Shape::Circle { radius } => 0.5 * mass * radius * radius,
             Shape::Rectangle { width, height } => { mass * (width * width + height * height) / 12.0
             }
+            Shape::Polygon { vertices } => {
+                // Sum of triangle fans around the centroid (the local origin)
+                let mut numerator = 0.0;
+                let mut denominator = 0.0;
+                for i in 0..vertices.len() {
+                    let a = vertices[i];
+                    let b = vertices[(i + 1) % vertices.len()];
+                    let cross = a.cross(&b).abs();
+                    numerator += cross * (a.dot(&a) + a.dot(&b) + b.dot(&b));
+                    denominator += cross;
+                }
+                if denominator == 0.0 {
+                    0.0
+                } else {
+                    mass * numerator / (6.0 * denominator)
+                }
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+fn polygon_signed_area(vertices: &[Vector2D]) -> f32 {
+    let mut area = 0.0;
+    for i in 0..vertices.len() {
+        area += vertices[i].cross(&vertices[(i + 1) % vertices.len()]);
+    }
+    area / 2.0
+}
+
+fn polygon_centroid(vertices: &[Vector2D]) -> Vector2D {
+    let mut centroid = Vector2D::zero();
+    let mut area = 0.0;
+    // Triangles fanned from the first vertex keep precision for polygons far from the origin
+    let origin = vertices[0];
+    for i in 1..vertices.len() - 1 {
+        let a = vertices[i] - origin;
+        let b = vertices[i + 1] - origin;
+        let triangle_area = a.cross(&b) / 2.0;
+        centroid += (a + b) * (triangle_area / 3.0);
+        area += triangle_area;
+    }
+    origin + centroid / area
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(coordinates: &[(f32, f32)]) -> Vec<Vector2D> {
+        coordinates.iter().map(|&(x, y)| Vector2D::new(x, y)).collect()
+    }
+
+    #[test]
+    fn polygon_rejects_invalid_vertex_lists() {
+        let cases = [
+            (points(&[(0.0, 0.0), (10.0, 0.0)]), ShapeError::TooFewVertices),
+            (points(&[(0.0, 0.0), (10.0, 0.0), (20.0, 0.0)]), ShapeError::Degenerate),
+            (points(&[(0.0, 0.0), (10.0, 0.0), (10.0, 0.0), (0.0, 10.0)]), ShapeError::Degenerate),
+            // Dart with one vertex pushed inwards
+            (points(&[(0.0, 0.0), (10.0, 5.0), (0.0, 10.0), (3.0, 5.0)]), ShapeError::NotConvex),
+            // A vertex in the middle of an edge isn't strictly convex
+            (points(&[(0.0, 0.0), (5.0, 0.0), (10.0, 0.0), (10.0, 10.0)]), ShapeError::NotConvex),
+            // Self-intersecting bow tie, lopsided so the loops don't cancel out to zero area
+            (points(&[(0.0, 0.0), (10.0, 10.0), (10.0, 0.0), (0.0, 20.0)]), ShapeError::NotConvex),
+        ];
+        for (vertices, error) in cases {
+            assert_eq!(Shape::polygon(vertices.clone()).unwrap_err(), error, "{vertices:?}");
+        }
+    }
+
+    #[test]
+    fn polygon_is_counter_clockwise_around_its_centroid() {
+        let clockwise = points(&[(0.0, 0.0), (0.0, 30.0), (30.0, 0.0)]);
+        let Ok(Shape::Polygon { vertices }) = Shape::polygon(clockwise) else { panic!("valid triangle rejected") };
+
+        assert!(polygon_signed_area(&vertices) > 0.0);
+        let centroid = vertices.iter().fold(Vector2D::zero(), |sum, &v| sum + v) / 3.0;
+        assert!(centroid.magnitude() < 1e-4);
+        assert!((polygon_signed_area(&vertices) - 450.0).abs() < 1e-3);
+    }
+}
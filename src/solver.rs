@@ -0,0 +1,500 @@
+use std::collections::HashMap;
+
+use crate::collision::ContactManifold;
+use crate::handle::BodyHandle;
+use crate::rigidbody::RigidBody;
+use crate::vector2d::Vector2D;
+
+/// Identifies a contact point across frames: (body a, body b, feature id)
+pub type ContactKey = (BodyHandle, BodyHandle, u32);
+
+/// Accumulated (normal, tangent) impulses of the previous step, keyed per contact point
+pub type ImpulseCache = HashMap<ContactKey, (f32, f32)>;
+
+/// Penetration (in pixels) allowed before position correction kicks in, keeps contacts alive
+pub(crate) const LINEAR_SLOP: f32 = 0.25;
+/// Fraction of the penetration removed per position iteration
+const BAUMGARTE: f32 = 0.2;
+/// Largest correction applied in one position iteration (pixels)
+pub(crate) const MAX_LINEAR_CORRECTION: f32 = 10.0;
+/// Approach speed (px/s) below which contacts don't bounce, 1 m/s at the 50 px per meter scale
+const RESTITUTION_THRESHOLD: f32 = 50.0;
+/// Above this condition number the two point block solver falls back to solving points one by one
+const MAX_CONDITION_NUMBER: f32 = 1000.0;
+
+/// Row major 2x2 matrix
+pub(crate) type Mat22 = [[f32; 2]; 2];
+
+struct ConstraintPoint {
+    r_a: Vector2D,
+    r_b: Vector2D,
+    // Contact point in each body's local frame, used to track separation during position solving
+    local_anchor_a: Vector2D,
+    local_anchor_b: Vector2D,
+    separation: f32,
+    normal_mass: f32,
+    tangent_mass: f32,
+    normal_impulse: f32,
+    tangent_impulse: f32,
+    velocity_bias: f32,
+    id: u32,
+}
+
+struct ContactConstraint {
+    handle_a: BodyHandle,
+    handle_b: BodyHandle,
+    // Positions of the bodies in the body slice passed to the solver
+    index_a: usize,
+    index_b: usize,
+    normal: Vector2D,
+    friction: f32,
+    points: Vec<ConstraintPoint>,
+    // Coupled normal mass matrix and its inverse for two point manifolds, solved as one block
+    block: Option<(Mat22, Mat22)>,
+}
+
+/// Sequential impulse solver over the contact manifolds of one step.
+/// Impulses are accumulated and clamped per contact point, and can be warm started
+/// from the impulses of the previous step.
+pub struct ContactSolver {
+    constraints: Vec<ContactConstraint>,
+}
+
+impl ContactSolver {
+    /// `index_of` maps the handles of the manifolds to positions in `bodies`
+    pub fn new(manifolds: &[ContactManifold], bodies: &[RigidBody], index_of: impl Fn(BodyHandle) -> usize,
+               cache: Option<&ImpulseCache>, dt: f32) -> Self {
+        let constraints = manifolds.iter()
+            .map(|manifold| {
+                let index_a = index_of(manifold.body_a);
+                let index_b = index_of(manifold.body_b);
+                let body_a = &bodies[index_a];
+                let body_b = &bodies[index_b];
+                let normal = manifold.normal;
+                let tangent = tangent_of(normal);
+                let restitution = (body_a.restitution + body_b.restitution) / 2.0;
+
+                let points: Vec<ConstraintPoint> = manifold.points.iter()
+                    .map(|contact| {
+                        let r_a = contact.point - body_a.position;
+                        let r_b = contact.point - body_b.position;
+
+                        let rn_a = r_a.cross(&normal);
+                        let rn_b = r_b.cross(&normal);
+                        let k_normal = body_a.inv_mass + body_b.inv_mass
+                            + body_a.inv_moment_of_inertia * rn_a * rn_a
+                            + body_b.inv_moment_of_inertia * rn_b * rn_b;
+
+                        let rt_a = r_a.cross(&tangent);
+                        let rt_b = r_b.cross(&tangent);
+                        let k_tangent = body_a.inv_mass + body_b.inv_mass
+                            + body_a.inv_moment_of_inertia * rt_a * rt_a
+                            + body_b.inv_moment_of_inertia * rt_b * rt_b;
+
+                        // Bounce only off contacts that approach fast enough
+                        let relative_velocity = body_b.velocity_at_point(contact.point)
+                            - body_a.velocity_at_point(contact.point);
+                        let approach = relative_velocity.dot(&normal);
+                        let velocity_bias = if approach < -RESTITUTION_THRESHOLD {
+                            -restitution * approach
+                        } else if contact.penetration < 0.0 {
+                            // Not touching yet (within the contact margin), allow closing the gap this step
+                            contact.penetration / dt
+                        } else {
+                            0.0
+                        };
+
+                        let key = (manifold.body_a, manifold.body_b, contact.id);
+                        let (normal_impulse, tangent_impulse) = cache
+                            .and_then(|cache| cache.get(&key).copied())
+                            .unwrap_or((0.0, 0.0));
+
+                        ConstraintPoint {
+                            r_a,
+                            r_b,
+                            local_anchor_a: r_a.rotate(-body_a.angle),
+                            local_anchor_b: r_b.rotate(-body_b.angle),
+                            separation: -contact.penetration,
+                            normal_mass: if k_normal > 0.0 { 1.0 / k_normal } else { 0.0 },
+                            tangent_mass: if k_tangent > 0.0 { 1.0 / k_tangent } else { 0.0 },
+                            normal_impulse,
+                            tangent_impulse,
+                            velocity_bias,
+                            id: contact.id,
+                        }
+                    })
+                    .collect();
+
+                let block = block_matrices(&points, normal, body_a, body_b);
+                ContactConstraint {
+                    handle_a: manifold.body_a,
+                    handle_b: manifold.body_b,
+                    index_a,
+                    index_b,
+                    normal,
+                    friction: (body_a.friction + body_b.friction) / 2.0,
+                    points,
+                    block,
+                }
+            })
+            .collect();
+
+        Self { constraints }
+    }
+
+    /// Re-applies the impulses carried over from the previous step
+    pub fn warm_start(&self, bodies: &mut [RigidBody]) {
+        for constraint in &self.constraints {
+            let tangent = tangent_of(constraint.normal);
+            let mut state = VelocityState::load(bodies, constraint.index_a, constraint.index_b);
+
+            for point in &constraint.points {
+                let impulse = constraint.normal * point.normal_impulse + tangent * point.tangent_impulse;
+                state.apply(impulse, point.r_a, point.r_b);
+            }
+
+            state.store(bodies, constraint.index_a, constraint.index_b);
+        }
+    }
+
+    pub fn solve_velocity_constraints(&mut self, bodies: &mut [RigidBody]) {
+        for constraint in &mut self.constraints {
+            let normal = constraint.normal;
+            let tangent = tangent_of(normal);
+            let mut state = VelocityState::load(bodies, constraint.index_a, constraint.index_b);
+
+            // Friction first, so the normal constraint (the more important one) gets the last word
+            for point in &mut constraint.points {
+                let relative_velocity = state.relative_velocity(point.r_a, point.r_b);
+                let lambda = -point.tangent_mass * relative_velocity.dot(&tangent);
+
+                // Coulomb's law, friction can't exceed mu times the normal impulse
+                let max_friction = constraint.friction * point.normal_impulse;
+                let new_impulse = (point.tangent_impulse + lambda).clamp(-max_friction, max_friction);
+                let lambda = new_impulse - point.tangent_impulse;
+                point.tangent_impulse = new_impulse;
+
+                state.apply(tangent * lambda, point.r_a, point.r_b);
+            }
+
+            if let Some((k, normal_mass)) = constraint.block {
+                solve_block(&mut constraint.points, normal, &k, &normal_mass, &mut state);
+            } else {
+                for point in &mut constraint.points {
+                    let relative_velocity = state.relative_velocity(point.r_a, point.r_b);
+                    let velocity_along_normal = relative_velocity.dot(&normal);
+                    let lambda = -point.normal_mass * (velocity_along_normal - point.velocity_bias);
+
+                    // Total impulse may only push the bodies apart
+                    let new_impulse = (point.normal_impulse + lambda).max(0.0);
+                    let lambda = new_impulse - point.normal_impulse;
+                    point.normal_impulse = new_impulse;
+
+                    state.apply(normal * lambda, point.r_a, point.r_b);
+                }
+            }
+
+            state.store(bodies, constraint.index_a, constraint.index_b);
+        }
+    }
+
+    /// One pass of position correction.
+    /// Returns true once every contact is within the allowed penetration.
+    pub fn solve_position_constraints(&self, bodies: &mut [RigidBody]) -> bool {
+        let mut min_separation = 0.0_f32;
+
+        for constraint in &self.constraints {
+            let normal = constraint.normal;
+            let mut state = PositionState::load(bodies, constraint.index_a, constraint.index_b);
+
+            // Correct both points of a manifold together, so equally deep points don't tip the bodies
+            let mut block_solved = false;
+            if let [first, second] = constraint.points.as_slice() {
+                let (r1_a, r1_b, separation_1) = state.anchors(first, normal);
+                let (r2_a, r2_b, separation_2) = state.anchors(second, normal);
+                min_separation = min_separation.min(separation_1).min(separation_2);
+
+                let c1 = position_correction(separation_1);
+                let c2 = position_correction(separation_2);
+
+                let (rn1_a, rn1_b) = (r1_a.cross(&normal), r1_b.cross(&normal));
+                let (rn2_a, rn2_b) = (r2_a.cross(&normal), r2_b.cross(&normal));
+                let masses = state.inv_mass_a + state.inv_mass_b;
+                let k11 = masses + state.inv_i_a * rn1_a * rn1_a + state.inv_i_b * rn1_b * rn1_b;
+                let k22 = masses + state.inv_i_a * rn2_a * rn2_a + state.inv_i_b * rn2_b * rn2_b;
+                let k12 = masses + state.inv_i_a * rn1_a * rn2_a + state.inv_i_b * rn1_b * rn2_b;
+                let determinant = k11 * k22 - k12 * k12;
+
+                if k11 * k11 < MAX_CONDITION_NUMBER * determinant && determinant != 0.0 {
+                    let impulse_1 = -(k22 * c1 - k12 * c2) / determinant;
+                    let impulse_2 = -(k11 * c2 - k12 * c1) / determinant;
+                    if impulse_1 >= 0.0 && impulse_2 >= 0.0 {
+                        state.apply(normal * impulse_1, r1_a, r1_b);
+                        state.apply(normal * impulse_2, r2_a, r2_b);
+                        block_solved = true;
+                    }
+                }
+            }
+
+            if !block_solved {
+                for point in &constraint.points {
+                    let (r_a, r_b, separation) = state.anchors(point, normal);
+                    min_separation = min_separation.min(separation);
+
+                    let correction = position_correction(separation);
+                    let rn_a = r_a.cross(&normal);
+                    let rn_b = r_b.cross(&normal);
+                    let k = state.inv_mass_a + state.inv_mass_b
+                        + state.inv_i_a * rn_a * rn_a
+                        + state.inv_i_b * rn_b * rn_b;
+                    let impulse = if k > 0.0 { -correction / k } else { 0.0 };
+
+                    state.apply(normal * impulse, r_a, r_b);
+                }
+            }
+
+            state.store(bodies, constraint.index_a, constraint.index_b);
+        }
+
+        min_separation >= -3.0 * LINEAR_SLOP
+    }
+
+    /// Accumulated impulses of this step, to warm start the next one
+    pub fn impulses(&self) -> ImpulseCache {
+        let mut cache = HashMap::new();
+        for constraint in &self.constraints {
+            for point in &constraint.points {
+                cache.insert(
+                    (constraint.handle_a, constraint.handle_b, point.id),
+                    (point.normal_impulse, point.tangent_impulse),
+                );
+            }
+        }
+        cache
+    }
+}
+
+/// Normal mass matrix `K` and its inverse for a two point manifold.
+/// `None` for single points or when the two points are nearly redundant (ill-conditioned `K`).
+fn block_matrices(points: &[ConstraintPoint], normal: Vector2D,
+                  body_a: &RigidBody, body_b: &RigidBody) -> Option<(Mat22, Mat22)> {
+    if points.len() != 2 {
+        return None;
+    }
+
+    let rn1_a = points[0].r_a.cross(&normal);
+    let rn1_b = points[0].r_b.cross(&normal);
+    let rn2_a = points[1].r_a.cross(&normal);
+    let rn2_b = points[1].r_b.cross(&normal);
+    let (m_a, i_a) = (body_a.inv_mass, body_a.inv_moment_of_inertia);
+    let (m_b, i_b) = (body_b.inv_mass, body_b.inv_moment_of_inertia);
+
+    let k11 = m_a + m_b + i_a * rn1_a * rn1_a + i_b * rn1_b * rn1_b;
+    let k22 = m_a + m_b + i_a * rn2_a * rn2_a + i_b * rn2_b * rn2_b;
+    let k12 = m_a + m_b + i_a * rn1_a * rn2_a + i_b * rn1_b * rn2_b;
+
+    let determinant = k11 * k22 - k12 * k12;
+    if k11 * k11 >= MAX_CONDITION_NUMBER * determinant || determinant == 0.0 {
+        return None;
+    }
+
+    let inv_det = 1.0 / determinant;
+    Some((
+        [[k11, k12], [k12, k22]],
+        [[k22 * inv_det, -k12 * inv_det], [-k12 * inv_det, k11 * inv_det]],
+    ))
+}
+
+/// Solves both normal impulses of a two point manifold at once as a small LCP.
+/// Tries each combination of active points until one satisfies
+/// `impulse >= 0`, `velocity >= 0` and `impulse * velocity = 0` for both points.
+fn solve_block(points: &mut [ConstraintPoint], normal: Vector2D,
+               k: &Mat22, normal_mass: &Mat22, state: &mut VelocityState) {
+    let old = [points[0].normal_impulse, points[1].normal_impulse];
+    let vn1 = state.relative_velocity(points[0].r_a, points[0].r_b).dot(&normal);
+    let vn2 = state.relative_velocity(points[1].r_a, points[1].r_b).dot(&normal);
+
+    // b = vn - bias - K * old, so that vn_new = K * x + b
+    let b1 = vn1 - points[0].velocity_bias - (k[0][0] * old[0] + k[0][1] * old[1]);
+    let b2 = vn2 - points[1].velocity_bias - (k[1][0] * old[0] + k[1][1] * old[1]);
+
+    let candidates = [
+        // Both points active
+        {
+            let x1 = -(normal_mass[0][0] * b1 + normal_mass[0][1] * b2);
+            let x2 = -(normal_mass[1][0] * b1 + normal_mass[1][1] * b2);
+            (x1, x2, 0.0, 0.0)
+        },
+        // Only the first point active
+        {
+            let x1 = -b1 / k[0][0];
+            (x1, 0.0, 0.0, k[1][0] * x1 + b2)
+        },
+        // Only the second point active
+        {
+            let x2 = -b2 / k[1][1];
+            (0.0, x2, k[0][1] * x2 + b1, 0.0)
+        },
+        // Both points separating
+        (0.0, 0.0, b1, b2),
+    ];
+
+    for (x1, x2, vn1, vn2) in candidates {
+        if x1 >= 0.0 && x2 >= 0.0 && vn1 >= 0.0 && vn2 >= 0.0 {
+            state.apply(normal * (x1 - old[0]), points[0].r_a, points[0].r_b);
+            state.apply(normal * (x2 - old[1]), points[1].r_a, points[1].r_b);
+            points[0].normal_impulse = x1;
+            points[1].normal_impulse = x2;
+            return;
+        }
+    }
+    // No combination solved the LCP (numerical trouble), keep the previous impulses
+}
+
+/// Baumgarte style correction for a separation, zero within the allowed slop
+fn position_correction(separation: f32) -> f32 {
+    (BAUMGARTE * (separation + LINEAR_SLOP)).clamp(-MAX_LINEAR_CORRECTION, 0.0)
+}
+
+/// Friction direction for a contact normal
+fn tangent_of(normal: Vector2D) -> Vector2D {
+    Vector2D::new(normal.y, -normal.x)
+}
+
+/// Local copy of the velocities of a constrained body pair, written back once solved
+pub(crate) struct VelocityState {
+    pub(crate) velocity_a: Vector2D,
+    pub(crate) angular_velocity_a: f32,
+    pub(crate) inv_mass_a: f32,
+    pub(crate) inv_i_a: f32,
+    pub(crate) velocity_b: Vector2D,
+    pub(crate) angular_velocity_b: f32,
+    pub(crate) inv_mass_b: f32,
+    pub(crate) inv_i_b: f32,
+}
+
+impl VelocityState {
+    pub(crate) fn load(bodies: &[RigidBody], index_a: usize, index_b: usize) -> Self {
+        let body_a = &bodies[index_a];
+        let body_b = &bodies[index_b];
+        Self {
+            velocity_a: body_a.velocity,
+            angular_velocity_a: body_a.angular_velocity,
+            inv_mass_a: body_a.inv_mass,
+            inv_i_a: body_a.inv_moment_of_inertia,
+            velocity_b: body_b.velocity,
+            angular_velocity_b: body_b.angular_velocity,
+            inv_mass_b: body_b.inv_mass,
+            inv_i_b: body_b.inv_moment_of_inertia,
+        }
+    }
+
+    pub(crate) fn store(&self, bodies: &mut [RigidBody], index_a: usize, index_b: usize) {
+        bodies[index_a].velocity = self.velocity_a;
+        bodies[index_a].angular_velocity = self.angular_velocity_a;
+        bodies[index_b].velocity = self.velocity_b;
+        bodies[index_b].angular_velocity = self.angular_velocity_b;
+    }
+
+    pub(crate) fn relative_velocity(&self, r_a: Vector2D, r_b: Vector2D) -> Vector2D {
+        self.velocity_b + r_b.perpendicular() * self.angular_velocity_b
+            - self.velocity_a - r_a.perpendicular() * self.angular_velocity_a
+    }
+
+    /// Applies `impulse` to B and its opposite to A
+    pub(crate) fn apply(&mut self, impulse: Vector2D, r_a: Vector2D, r_b: Vector2D) {
+        self.velocity_a -= impulse * self.inv_mass_a;
+        self.angular_velocity_a -= self.inv_i_a * r_a.cross(&impulse);
+        self.velocity_b += impulse * self.inv_mass_b;
+        self.angular_velocity_b += self.inv_i_b * r_b.cross(&impulse);
+    }
+
+    /// Applies a linear impulse with separately computed angular parts, to B and its opposite to A
+    pub(crate) fn apply_parts(&mut self, impulse: Vector2D, angular_a: f32, angular_b: f32) {
+        self.velocity_a -= impulse * self.inv_mass_a;
+        self.angular_velocity_a -= self.inv_i_a * angular_a;
+        self.velocity_b += impulse * self.inv_mass_b;
+        self.angular_velocity_b += self.inv_i_b * angular_b;
+    }
+
+    /// Applies an angular impulse to B and its opposite to A
+    pub(crate) fn apply_angular(&mut self, impulse: f32) {
+        self.angular_velocity_a -= self.inv_i_a * impulse;
+        self.angular_velocity_b += self.inv_i_b * impulse;
+    }
+}
+
+/// Local copy of the positions of a constrained body pair, written back once corrected
+pub(crate) struct PositionState {
+    pub(crate) position_a: Vector2D,
+    pub(crate) angle_a: f32,
+    pub(crate) inv_mass_a: f32,
+    pub(crate) inv_i_a: f32,
+    pub(crate) position_b: Vector2D,
+    pub(crate) angle_b: f32,
+    pub(crate) inv_mass_b: f32,
+    pub(crate) inv_i_b: f32,
+}
+
+impl PositionState {
+    pub(crate) fn load(bodies: &[RigidBody], index_a: usize, index_b: usize) -> Self {
+        let body_a = &bodies[index_a];
+        let body_b = &bodies[index_b];
+        Self {
+            position_a: body_a.position,
+            angle_a: body_a.angle,
+            inv_mass_a: body_a.inv_mass,
+            inv_i_a: body_a.inv_moment_of_inertia,
+            position_b: body_b.position,
+            angle_b: body_b.angle,
+            inv_mass_b: body_b.inv_mass,
+            inv_i_b: body_b.inv_moment_of_inertia,
+        }
+    }
+
+    pub(crate) fn store(&self, bodies: &mut [RigidBody], index_a: usize, index_b: usize) {
+        bodies[index_a].position = self.position_a;
+        bodies[index_a].angle = self.angle_a;
+        bodies[index_b].position = self.position_b;
+        bodies[index_b].angle = self.angle_b;
+    }
+
+    /// Current lever arms of a contact point and its separation,
+    /// from how far the anchors moved apart since detection
+    fn anchors(&self, point: &ConstraintPoint, normal: Vector2D) -> (Vector2D, Vector2D, f32) {
+        let r_a = point.local_anchor_a.rotate(self.angle_a);
+        let r_b = point.local_anchor_b.rotate(self.angle_b);
+        let separation = ((self.position_b + r_b) - (self.position_a + r_a)).dot(&normal) + point.separation;
+        (r_a, r_b, separation)
+    }
+
+    /// Lever arms of two body local anchors at the current angles,
+    /// and the vector from the anchor on A to the anchor on B
+    pub(crate) fn anchor_offsets(&self, local_anchor_a: Vector2D, local_anchor_b: Vector2D) -> (Vector2D, Vector2D, Vector2D) {
+        let r_a = local_anchor_a.rotate(self.angle_a);
+        let r_b = local_anchor_b.rotate(self.angle_b);
+        (r_a, r_b, (self.position_b + r_b) - (self.position_a + r_a))
+    }
+
+    /// Applies a position impulse to B and its opposite to A
+    pub(crate) fn apply(&mut self, impulse: Vector2D, r_a: Vector2D, r_b: Vector2D) {
+        self.position_a -= impulse * self.inv_mass_a;
+        self.angle_a -= self.inv_i_a * r_a.cross(&impulse);
+        self.position_b += impulse * self.inv_mass_b;
+        self.angle_b += self.inv_i_b * r_b.cross(&impulse);
+    }
+
+    /// Applies a linear position impulse with separately computed angular parts, to B and its opposite to A
+    pub(crate) fn apply_parts(&mut self, impulse: Vector2D, angular_a: f32, angular_b: f32) {
+        self.position_a -= impulse * self.inv_mass_a;
+        self.angle_a -= self.inv_i_a * angular_a;
+        self.position_b += impulse * self.inv_mass_b;
+        self.angle_b += self.inv_i_b * angular_b;
+    }
+
+    /// Applies an angular position impulse to B and its opposite to A
+    pub(crate) fn apply_angular(&mut self, impulse: f32) {
+        self.angle_a -= self.inv_i_a * impulse;
+        self.angle_b += self.inv_i_b * impulse;
+    }
+}
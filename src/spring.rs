@@ -0,0 +1,98 @@
+use crate::handle::BodyHandle;
+use crate::rigidbody::RigidBody;
+use crate::vector2d::Vector2D;
+
+/// Far end of a spring
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpringEnd {
+    /// Anchor on a body, relative to its center in the body's frame
+    Body { body: BodyHandle, local_anchor: Vector2D },
+    /// Fixed world space point
+    Point(Vector2D),
+}
+
+/// Damped Hookean spring, a force generator the world evaluates every step before integration.
+/// Pulls (or pushes) its ends towards `rest_length` with `stiffness` (force per pixel of stretch),
+/// and resists their relative speed along the spring with `damping` (force per px/s).
+/// Unlike a joint the force is explicit, so very stiff springs on light bodies can overshoot.
+#[derive(Debug, Clone)]
+pub struct Spring {
+    pub body_a: BodyHandle,
+    /// Anchor on body A, relative to its center in the body's frame
+    pub local_anchor_a: Vector2D,
+    pub end: SpringEnd,
+    pub rest_length: f32,
+    pub stiffness: f32,
+    pub damping: f32,
+}
+
+impl Spring {
+    /// Spring between anchors on two bodies
+    pub fn new(body_a: BodyHandle, local_anchor_a: Vector2D, body_b: BodyHandle, local_anchor_b: Vector2D,
+               rest_length: f32, stiffness: f32, damping: f32) -> Self {
+        Self {
+            body_a,
+            local_anchor_a,
+            end: SpringEnd::Body { body: body_b, local_anchor: local_anchor_b },
+            rest_length,
+            stiffness,
+            damping,
+        }
+    }
+
+    /// Spring tethering an anchor on a body to a fixed world point
+    pub fn to_point(body_a: BodyHandle, local_anchor_a: Vector2D, point: Vector2D,
+                    rest_length: f32, stiffness: f32, damping: f32) -> Self {
+        Self {
+            body_a,
+            local_anchor_a,
+            end: SpringEnd::Point(point),
+            rest_length,
+            stiffness,
+            damping,
+        }
+    }
+
+    /// The body on each end, `None` for a spring to a world point
+    pub fn bodies(&self) -> (BodyHandle, Option<BodyHandle>) {
+        match self.end {
+            SpringEnd::Body { body, .. } => (self.body_a, Some(body)),
+            SpringEnd::Point(_) => (self.body_a, None),
+        }
+    }
+
+    /// Whether the spring is attached to the body
+    pub fn connects(&self, handle: BodyHandle) -> bool {
+        let (body_a, body_b) = self.bodies();
+        body_a == handle || body_b == Some(handle)
+    }
+
+    /// World space end points, `body_b` is ignored for a spring to a world point
+    pub fn anchors(&self, body_a: &RigidBody, body_b: Option<&RigidBody>) -> (Vector2D, Vector2D) {
+        let anchor_a = body_a.world_point(self.local_anchor_a);
+        let anchor_b = match (self.end, body_b) {
+            (SpringEnd::Body { local_anchor, .. }, Some(body_b)) => body_b.world_point(local_anchor),
+            (SpringEnd::Point(point), _) => point,
+            (SpringEnd::Body { .. }, None) => anchor_a,
+        };
+        (anchor_a, anchor_b)
+    }
+
+    /// Force on body A and the world space points it acts at on A and B.
+    /// Body B receives the opposite force.
+    pub(crate) fn force(&self, body_a: &RigidBody, body_b: Option<&RigidBody>) -> (Vector2D, Vector2D, Vector2D) {
+        let (anchor_a, anchor_b) = self.anchors(body_a, body_b);
+        let offset = anchor_b - anchor_a;
+        let length = offset.magnitude();
+        // Ends on top of each other, no direction to push along
+        if length <= f32::EPSILON {
+            return (Vector2D::zero(), anchor_a, anchor_b);
+        }
+        let direction = offset / length;
+
+        let velocity_b = body_b.map_or(Vector2D::zero(), |body| body.velocity_at_point(anchor_b));
+        let speed = (velocity_b - body_a.velocity_at_point(anchor_a)).dot(&direction);
+        let magnitude = self.stiffness * (length - self.rest_length) + self.damping * speed;
+        (direction * magnitude, anchor_a, anchor_b)
+    }
+}
@@ -0,0 +1,251 @@
+use crate::collision::{edge_normal, max_separation};
+use crate::shape::Shape;
+use crate::vector2d::Vector2D;
+
+/// Gap (pixels) at which two sweeping shapes count as touching
+const TOI_TOLERANCE: f32 = 0.05;
+/// Conservative advancement iterations before giving up on a sweep
+const MAX_TOI_ITERATIONS: usize = 50;
+
+/// Motion of a shape over a time interval, position and angle are interpolated linearly
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sweep {
+    pub start_position: Vector2D,
+    pub start_angle: f32,
+    pub end_position: Vector2D,
+    pub end_angle: f32,
+}
+
+impl Sweep {
+    pub fn new(start_position: Vector2D, start_angle: f32, end_position: Vector2D, end_angle: f32) -> Self {
+        Self { start_position, start_angle, end_position, end_angle }
+    }
+
+    /// A shape that doesn't move
+    pub fn fixed(position: Vector2D, angle: f32) -> Self {
+        Self::new(position, angle, position, angle)
+    }
+
+    /// (position, angle) at `fraction` (0..=1) of the sweep
+    pub fn transform(&self, fraction: f32) -> (Vector2D, f32) {
+        let position = self.start_position + (self.end_position - self.start_position) * fraction;
+        let angle = self.start_angle + (self.end_angle - self.start_angle) * fraction;
+        (position, angle)
+    }
+}
+
+/// Closest points between two shapes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Distance {
+    /// Gap between the shapes, 0 when they overlap
+    pub distance: f32,
+    /// Closest point on A, in world space
+    pub point_a: Vector2D,
+    /// Closest point on B, in world space
+    pub point_b: Vector2D,
+    /// Unit direction from A to B, zero when the shapes overlap
+    pub normal: Vector2D,
+}
+
+/// First contact of two sweeping shapes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeOfImpact {
+    /// Fraction of the sweeps (0..=1) at which the shapes touch
+    pub fraction: f32,
+    /// Contact point, in world space
+    pub point: Vector2D,
+    /// Contact normal from A to B, zero when the shapes already overlap at the start
+    pub normal: Vector2D,
+}
+
+/// Closest points between shape A at (`position_a`, `angle_a`) and shape B at (`position_b`, `angle_b`)
+pub fn distance(shape_a: &Shape, position_a: Vector2D, angle_a: f32,
+                shape_b: &Shape, position_b: Vector2D, angle_b: f32) -> Distance {
+    // A circle is its center point grown by the radius, a polygon has no rounding
+    let (core_a, radius_a) = core(shape_a, position_a, angle_a);
+    let (core_b, radius_b) = core(shape_b, position_b, angle_b);
+
+    let (core_distance, point_a, point_b) = core_distance(&core_a, &core_b);
+    if core_distance <= radius_a + radius_b {
+        let midpoint = (point_a + point_b) * 0.5;
+        return Distance { distance: 0.0, point_a: midpoint, point_b: midpoint, normal: Vector2D::zero() };
+    }
+
+    let normal = (point_b - point_a) / core_distance;
+    Distance {
+        distance: core_distance - radius_a - radius_b,
+        point_a: point_a + normal * radius_a,
+        point_b: point_b - normal * radius_b,
+        normal,
+    }
+}
+
+/// First time two shapes moving along their sweeps touch, `None` if they don't within the sweeps.
+/// Uses conservative advancement: steps forward by as much as the current gap allows
+/// given how fast any point of either shape can close it, so it can't tunnel through thin shapes.
+/// Also `None` for shapes spinning too fast to settle it within the iteration limit.
+pub fn time_of_impact(shape_a: &Shape, sweep_a: &Sweep, shape_b: &Shape, sweep_b: &Sweep) -> Option<TimeOfImpact> {
+    match sweep_impact(shape_a, sweep_a, shape_b, sweep_b) {
+        SweepImpact::Hit(hit) => Some(hit),
+        SweepImpact::Miss | SweepImpact::Stalled(_) => None,
+    }
+}
+
+/// Outcome of `sweep_impact`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum SweepImpact {
+    Hit(TimeOfImpact),
+    Miss,
+    /// Out of iterations, the shapes don't touch before this fraction but may after it
+    Stalled(f32),
+}
+
+/// `time_of_impact` telling apart a miss from giving up, so a caller can sweep on from where it stalled
+pub(crate) fn sweep_impact(shape_a: &Shape, sweep_a: &Sweep, shape_b: &Shape, sweep_b: &Sweep) -> SweepImpact {
+    // Upper bound on how far any point of one shape moves towards the other over the whole sweep,
+    // besides the relative motion of the centers
+    let rotation_bound = (sweep_a.end_angle - sweep_a.start_angle).abs() * rotation_reach(shape_a)
+        + (sweep_b.end_angle - sweep_b.start_angle).abs() * rotation_reach(shape_b);
+    let relative_motion = (sweep_b.end_position - sweep_b.start_position)
+        - (sweep_a.end_position - sweep_a.start_position);
+
+    let mut fraction = 0.0_f32;
+    let mut iterations = 0;
+    loop {
+        let (position_a, angle_a) = sweep_a.transform(fraction);
+        let (position_b, angle_b) = sweep_b.transform(fraction);
+        let gap = distance(shape_a, position_a, angle_a, shape_b, position_b, angle_b);
+
+        if gap.distance <= TOI_TOLERANCE {
+            return SweepImpact::Hit(TimeOfImpact {
+                fraction,
+                point: (gap.point_a + gap.point_b) * 0.5,
+                normal: gap.normal,
+            });
+        }
+        // Fast spinning shapes advance slowly, the bound on their closing speed is loose
+        iterations += 1;
+        if iterations == MAX_TOI_ITERATIONS {
+            return SweepImpact::Stalled(fraction);
+        }
+
+        // Closing speed along the normal, per unit of the sweep
+        let approach = rotation_bound - relative_motion.dot(&gap.normal);
+        if approach <= 0.0 {
+            return SweepImpact::Miss;
+        }
+        // Stop just short of touching so the next distance is still positive
+        fraction += (gap.distance - 0.5 * TOI_TOLERANCE) / approach;
+        if fraction > 1.0 {
+            return SweepImpact::Miss;
+        }
+    }
+}
+
+/// Inner shape of a circle or polygon, the rest is a uniform rounding radius around it
+enum Core {
+    Point(Vector2D),
+    /// World space vertices, counter-clockwise
+    Polygon(Vec<Vector2D>),
+}
+
+fn core(shape: &Shape, position: Vector2D, angle: f32) -> (Core, f32) {
+    match shape {
+        Shape::Circle { radius } => (Core::Point(position), *radius),
+        _ => {
+            let vertices = shape.vertices().into_iter()
+                .map(|v| position + v.rotate(angle))
+                .collect();
+            (Core::Polygon(vertices), 0.0)
+        }
+    }
+}
+
+/// Distance between two cores with their closest points, 0 when they overlap
+fn core_distance(a: &Core, b: &Core) -> (f32, Vector2D, Vector2D) {
+    match (a, b) {
+        (Core::Point(a), Core::Point(b)) => ((*b - *a).magnitude(), *a, *b),
+        (Core::Point(point), Core::Polygon(vertices)) => {
+            let (distance, closest) = point_polygon(*point, vertices);
+            (distance, *point, closest)
+        }
+        (Core::Polygon(vertices), Core::Point(point)) => {
+            let (distance, closest) = point_polygon(*point, vertices);
+            (distance, closest, *point)
+        }
+        (Core::Polygon(a), Core::Polygon(b)) => {
+            // Overlapping unless some face separates them
+            if max_separation(a, b).1 <= 0.0 && max_separation(b, a).1 <= 0.0 {
+                return (0.0, a[0], a[0]);
+            }
+            // Separated convex polygons are closest between a vertex of one and an edge of the other
+            let mut best = (f32::MAX, a[0], b[0]);
+            for &vertex in a {
+                let (distance, closest) = point_boundary(vertex, b);
+                if distance < best.0 {
+                    best = (distance, vertex, closest);
+                }
+            }
+            for &vertex in b {
+                let (distance, closest) = point_boundary(vertex, a);
+                if distance < best.0 {
+                    best = (distance, closest, vertex);
+                }
+            }
+            best
+        }
+    }
+}
+
+/// Distance from a point to a convex polygon and the closest point on it, 0 inside
+fn point_polygon(point: Vector2D, vertices: &[Vector2D]) -> (f32, Vector2D) {
+    if max_separation(vertices, &[point]).1 <= 0.0 {
+        return (0.0, point);
+    }
+    point_boundary(point, vertices)
+}
+
+/// Distance from a point to the closest edge of a polygon and the closest point on that edge
+fn point_boundary(point: Vector2D, vertices: &[Vector2D]) -> (f32, Vector2D) {
+    let mut best = (f32::MAX, vertices[0]);
+    for i in 0..vertices.len() {
+        let v1 = vertices[i];
+        let edge = vertices[(i + 1) % vertices.len()] - v1;
+        let t = ((point - v1).dot(&edge) / edge.magnitude_squared()).clamp(0.0, 1.0);
+        let closest = v1 + edge * t;
+        let distance = (point - closest).magnitude();
+        if distance < best.0 {
+            best = (distance, closest);
+        }
+    }
+    best
+}
+
+/// Distance from the shape's center to its furthest point
+pub(crate) fn bounding_radius(shape: &Shape) -> f32 {
+    match shape {
+        Shape::Circle { radius } => *radius,
+        _ => shape.vertices().iter().map(|v| v.magnitude()).fold(0.0, f32::max),
+    }
+}
+
+/// How far a point of the shape can move per radian it turns, a circle looks the same at any angle
+fn rotation_reach(shape: &Shape) -> f32 {
+    match shape {
+        Shape::Circle { .. } => 0.0,
+        _ => bounding_radius(shape),
+    }
+}
+
+/// Distance from the shape's center to its closest edge
+pub(crate) fn inner_radius(shape: &Shape) -> f32 {
+    match shape {
+        Shape::Circle { radius } => *radius,
+        _ => {
+            let vertices = shape.vertices();
+            (0..vertices.len())
+                .map(|i| edge_normal(&vertices, i).dot(&vertices[i]))
+                .fold(f32::MAX, f32::min)
+        }
+    }
+}
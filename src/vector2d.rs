@@ -16,7 +16,6 @@ pub struct Vector2D {
 
 impl Vector2D {
     /// 2D vector with x and y coordinates
-
     pub fn new(x: f32, y:f32) -> Self {
         // Initiaze a new vector2d object
         Self { x, y }
@@ -52,6 +51,17 @@ impl Vector2D {
     pub fn cross(&self, other: &Vector2D) -> f32 {
         self.x * other.y - self.y * other.x
     }
+
+    /// Vector rotated 90 degrees counter-clockwise, `w x r` is `r.perpendicular() * w`
+    pub fn perpendicular(&self) -> Self {
+        Self { x: -self.y, y: self.x }
+    }
+
+    /// Rotate the vector by `angle` radians
+    pub fn rotate(&self, angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self { x: self.x * cos - self.y * sin, y: self.x * sin + self.y * cos }
+    }
 }
 
 // Implement arithmetic operations
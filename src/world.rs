@@ -1,129 +1,1260 @@
+use std::collections::HashSet;
+
 use crate::vector2d::Vector2D;
 use crate::rigidbody::RigidBody;
-use crate::collision::{Contact, detect_collision};
+use crate::shape::Shape;
+use crate::collision::{detect_collision, detect_collision_within, CollisionFilter, ContactManifold, PairFilter, CONTACT_MARGIN};
+use crate::solver::{ContactSolver, ImpulseCache};
+use crate::aabb::Aabb;
+use crate::broadphase::BroadPhase;
+use crate::dynamic_tree::DynamicTree;
+use crate::events::{ContactEvent, ContactEventKind, SensorEvent, SensorEventKind};
+use crate::handle::{BodyHandle, JointHandle, SpringHandle};
+use crate::joint::{Joint, JointConstraint};
+use crate::spring::Spring;
+use crate::solver::{PositionState, VelocityState};
+use crate::island::Islands;
+use crate::query::{RayControl, RayHit, ShapeCastHit};
+use crate::toi::{bounding_radius, inner_radius, sweep_impact, time_of_impact, Sweep, SweepImpact, TimeOfImpact};
+use crate::integrator::{damping_factor, rk4, ForceField, Integrator};
+
+/// Most impacts resolved per bullet per step, a bullet caught in a corner stops there
+const MAX_BULLET_SUBSTEPS: usize = 8;
+
+/// Entry of the handle table, `body` is the position in `World::bodies` while occupied
+#[derive(Debug, Clone, Copy)]
+struct BodySlot {
+    generation: u32,
+    body: Option<usize>,
+}
+
+/// Entry of the joint table
+#[derive(Debug)]
+struct JointSlot {
+    generation: u32,
+    joint: Option<Joint>,
+}
+
+/// Entry of the spring table
+#[derive(Debug)]
+struct SpringSlot {
+    generation: u32,
+    spring: Option<Spring>,
+}
 
 pub struct World {
-    pub bodies: Vec<RigidBody>,
+    // Densely packed, removing a body moves the last one into its place
+    bodies: Vec<RigidBody>,
+    // Handle of each entry of `bodies`
+    handles: Vec<BodyHandle>,
+    slots: Vec<BodySlot>,
+    free_slots: Vec<usize>,
+    joints: Vec<JointSlot>,
+    free_joint_slots: Vec<usize>,
+    springs: Vec<SpringSlot>,
+    free_spring_slots: Vec<usize>,
     pub gravity: Vector2D,
+    /// Linear damping coefficient (1/s), velocities decay as `e^(-damping * t)` whatever the step size
     pub damping: f32,
+    /// Angular damping coefficient (1/s)
     pub angular_damping: f32,
+    pub integrator: Integrator,
+    /// Step size used by `advance`, must be positive
+    pub fixed_dt: f32,
+    /// Most steps `advance` runs per call, time beyond that is dropped so a slow frame
+    /// can't snowball into ever more steps
+    pub max_substeps: usize,
+    accumulator: f32,
+    alpha: f32,
+    force_fields: Vec<ForceField>,
+    pair_filter: Option<PairFilter>,
+    /// Solver passes over the contacts per step, more passes give stiffer stacks
+    pub velocity_iterations: usize,
+    /// Maximum position correction passes per step (stops early once contacts are resolved)
+    pub position_iterations: usize,
+    /// Start each step from the previous step's contact impulses
+    pub warm_starting: bool,
+    /// Let islands of resting bodies fall asleep, sleeping bodies cost next to nothing until woken up
+    pub allow_sleeping: bool,
+    /// Speed (px/s) below which a body counts as resting
+    pub linear_sleep_tolerance: f32,
+    /// Angular speed (rad/s) below which a body counts as resting
+    pub angular_sleep_tolerance: f32,
+    /// Seconds every body of an island has to rest before the island falls asleep
+    pub time_to_sleep: f32,
+    contact_impulses: ImpulseCache,
+    contacts: Vec<ContactManifold>,
+    contact_events: Vec<ContactEvent>,
+    // (sensor, visitor) pairs overlapping as of the last step
+    sensor_overlaps: HashSet<(BodyHandle, BodyHandle)>,
+    sensor_events: Vec<SensorEvent>,
+    broadphase: Box<dyn BroadPhase>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl World {
     pub fn new() -> Self {
         Self {
             bodies: Vec::new(),
+            handles: Vec::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            joints: Vec::new(),
+            free_joint_slots: Vec::new(),
+            springs: Vec::new(),
+            free_spring_slots: Vec::new(),
             gravity: Vector2D::new(0.0, 9.81 * 50.0), // Scaled for screen coordinates
-            damping: 0.99,
-            angular_damping: 0.99,
+            // About 1% velocity loss per step at 60 fps
+            damping: 0.6,
+            angular_damping: 0.6,
+            integrator: Integrator::default(),
+            fixed_dt: 1.0 / 60.0,
+            max_substeps: 8,
+            accumulator: 0.0,
+            alpha: 1.0,
+            force_fields: Vec::new(),
+            pair_filter: None,
+            velocity_iterations: 8,
+            position_iterations: 3,
+            warm_starting: true,
+            allow_sleeping: true,
+            linear_sleep_tolerance: 2.5,
+            angular_sleep_tolerance: 2.0_f32.to_radians(),
+            time_to_sleep: 0.5,
+            contact_impulses: ImpulseCache::new(),
+            contacts: Vec::new(),
+            contact_events: Vec::new(),
+            sensor_overlaps: HashSet::new(),
+            sensor_events: Vec::new(),
+            broadphase: Box::new(DynamicTree::new()),
         }
     }
-    
-    pub fn add_body(&mut self, body: RigidBody) -> usize {
+
+    /// Replaces the broadphase, bodies are inserted into it on the next step
+    pub fn set_broadphase(&mut self, broadphase: impl BroadPhase + 'static) {
+        self.broadphase = Box::new(broadphase);
+    }
+
+    /// Adds an acceleration field applied to every dynamic body on top of gravity
+    pub fn add_force_field(&mut self, field: impl Fn(Vector2D, Vector2D) -> Vector2D + 'static) {
+        self.force_fields.push(Box::new(field));
+    }
+
+    /// Sets a callback deciding whether two bodies may collide, on top of their `CollisionFilter`s.
+    /// Also applies to sensors.
+    pub fn set_pair_filter(&mut self, filter: impl Fn(BodyHandle, &RigidBody, BodyHandle, &RigidBody) -> bool + 'static) {
+        self.pair_filter = Some(Box::new(filter));
+    }
+
+    pub fn clear_pair_filter(&mut self) {
+        self.pair_filter = None;
+    }
+
+    /// Bodies whose bounding box overlaps `aabb` and that would collide with a body using `filter`.
+    /// Like all queries this skips sensors, and finds bodies added since the last step only once it ran.
+    pub fn query_aabb(&self, aabb: &Aabb, filter: &CollisionFilter) -> Vec<BodyHandle> {
+        self.query_candidates(aabb, filter)
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    /// Bodies whose shape contains `point`, filtered like `query_aabb`
+    pub fn query_point(&self, point: Vector2D, filter: &CollisionFilter) -> Vec<BodyHandle> {
+        self.query_candidates(&Aabb::new(point, point), filter)
+            .filter(|(_, body)| body.contains_point(point))
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    /// Bodies overlapping `shape` placed at `position` rotated by `angle`, filtered like `query_aabb`.
+    /// Shapes that only touch don't count.
+    pub fn query_shape(&self, shape: &Shape, position: Vector2D, angle: f32, filter: &CollisionFilter) -> Vec<BodyHandle> {
+        let mut probe = RigidBody::new(position, shape.clone(), 0.0);
+        probe.angle = angle;
+        self.query_candidates(&probe.aabb(), filter)
+            .filter(|&(handle, body)| {
+                detect_collision(&probe, body, handle, handle).is_some_and(|manifold| manifold.is_overlapping())
+            })
+            .map(|(handle, _)| handle)
+            .collect()
+    }
+
+    /// First body `shape` (at a fixed `angle`) runs into when moved from `start` to `end`,
+    /// filtered like `query_aabb`. Bodies are taken where they are now.
+    pub fn shape_cast(&self, shape: &Shape, angle: f32, start: Vector2D, end: Vector2D,
+                      filter: &CollisionFilter) -> Option<ShapeCastHit> {
+        let sweep = Sweep::new(start, angle, end, angle);
+        let swept = shape.aabb(start, angle).union(&shape.aabb(end, angle));
+        self.query_candidates(&swept, filter)
+            .filter_map(|(handle, body)| {
+                let toi = time_of_impact(shape, &sweep, &body.shape, &Sweep::fixed(body.position, body.angle))?;
+                Some(ShapeCastHit { body: handle, point: toi.point, normal: toi.normal * -1.0, fraction: toi.fraction })
+            })
+            .min_by(|a, b| a.fraction.total_cmp(&b.fraction))
+    }
+
+    /// Non-sensor bodies passing `filter` whose current bounding box overlaps `aabb`
+    fn query_candidates<'a>(&'a self, aabb: &Aabb, filter: &'a CollisionFilter)
+        -> impl Iterator<Item = (BodyHandle, &'a RigidBody)> + 'a {
+        let aabb = *aabb;
+        self.broadphase.query_aabb(&aabb)
+            .into_iter()
+            .filter_map(|slot| self.slots[slot].body)
+            .map(|index| (self.handles[index], &self.bodies[index]))
+            .filter(move |(_, body)| {
+                !body.is_sensor && filter.should_collide(&body.filter) && body.aabb().overlaps(&aabb)
+            })
+    }
+
+    /// Closest body hit by the ray from `origin` along `direction` within `max_distance`.
+    /// Sensors are skipped, uses the bounding boxes of the last step to find candidates.
+    pub fn raycast(&self, origin: Vector2D, direction: Vector2D, max_distance: f32) -> Option<RayHit> {
+        // With every hit clipping the ray, the last hit reported is the closest
+        let mut closest = None;
+        self.raycast_with(origin, direction, max_distance, |hit| {
+            closest = Some(*hit);
+            RayControl::Clip
+        });
+        closest
+    }
+
+    /// Every body hit by the ray, nearest first
+    pub fn raycast_all(&self, origin: Vector2D, direction: Vector2D, max_distance: f32) -> Vec<RayHit> {
+        let mut hits = Vec::new();
+        self.raycast_with(origin, direction, max_distance, |hit| {
+            hits.push(*hit);
+            RayControl::Continue
+        });
+        hits.sort_by(|a, b| a.fraction.total_cmp(&b.fraction));
+        hits
+    }
+
+    /// Reports the bodies hit by the ray to `callback` in no particular order,
+    /// which decides after each hit whether to clip the ray, keep going or stop
+    pub fn raycast_with(&self, origin: Vector2D, direction: Vector2D, max_distance: f32,
+                        mut callback: impl FnMut(&RayHit) -> RayControl) {
+        let direction = direction.normalize();
+        if direction.magnitude_squared() == 0.0 || max_distance <= 0.0 {
+            return;
+        }
+
+        let mut length = max_distance;
+        for slot in self.broadphase.query_ray(origin, direction, max_distance) {
+            let Some(index) = self.slots[slot].body else { continue };
+            let body = &self.bodies[index];
+            if body.is_sensor {
+                continue;
+            }
+            let Some((distance, normal)) = body.raycast(origin, direction, length) else { continue };
+            let hit = RayHit {
+                body: self.handles[index],
+                point: origin + direction * distance,
+                normal,
+                fraction: distance / max_distance,
+            };
+            match callback(&hit) {
+                RayControl::Clip => length = distance,
+                RayControl::Continue => {}
+                RayControl::Stop => return,
+            }
+        }
+    }
+
+    pub fn add_body(&mut self, mut body: RigidBody) -> BodyHandle {
+        // Nothing to interpolate from yet
+        body.previous_position = body.position;
+        body.previous_angle = body.angle;
+
+
+        let slot = match self.free_slots.pop() {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(BodySlot { generation: 0, body: None });
+                self.slots.len() - 1
+            }
+        };
+        self.slots[slot].body = Some(self.bodies.len());
+
+        let handle = BodyHandle::new(slot, self.slots[slot].generation);
         self.bodies.push(body);
-        self.bodies.len() - 1
+        self.handles.push(handle);
+        handle
+    }
+
+    /// Removes the body and the joints and springs attached to it and returns it, `None` if the handle is stale
+    pub fn remove_body(&mut self, handle: BodyHandle) -> Option<RigidBody> {
+        let index = self.body_index(handle)?;
+
+        let attached: Vec<JointHandle> = self.joints()
+            .filter(|(_, joint)| {
+                let (body_a, body_b) = joint.bodies();
+                body_a == handle || body_b == handle
+            })
+            .map(|(joint_handle, _)| joint_handle)
+            .collect();
+        for joint_handle in attached {
+            self.remove_joint(joint_handle);
+        }
+        let attached: Vec<SpringHandle> = self.springs()
+            .filter(|(_, spring)| spring.connects(handle))
+            .map(|(spring_handle, _)| spring_handle)
+            .collect();
+        for spring_handle in attached {
+            self.remove_spring(spring_handle);
+        }
+        // Whatever rested on the body has to notice it's gone
+        self.wake_touching(handle);
+
+        let slot = &mut self.slots[handle.index()];
+        slot.body = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(handle.index());
+        self.broadphase.remove(handle.index());
+
+        // The last body moves into the freed place
+        let body = self.bodies.swap_remove(index);
+        self.handles.swap_remove(index);
+        if let Some(moved) = self.handles.get(index) {
+            self.slots[moved.index()].body = Some(index);
+        }
+        Some(body)
+    }
+
+    /// Removes all bodies, joints and springs, handles to them stop resolving.
+    /// Every contact and sensor overlap ends with an `End` or `Exit` event, queued after the undrained ones.
+    pub fn clear(&mut self) {
+        for (index, slot) in self.joints.iter_mut().enumerate() {
+            if slot.joint.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free_joint_slots.push(index);
+            }
+        }
+        for (index, slot) in self.springs.iter_mut().enumerate() {
+            if slot.spring.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free_spring_slots.push(index);
+            }
+        }
+        for handle in self.handles.drain(..) {
+            let slot = &mut self.slots[handle.index()];
+            slot.body = None;
+            slot.generation = slot.generation.wrapping_add(1);
+            self.free_slots.push(handle.index());
+        }
+        self.bodies.clear();
+        self.broadphase.clear();
+        for manifold in self.contacts.drain(..) {
+            self.contact_events.push(ContactEvent::new(ContactEventKind::End, &manifold, &self.contact_impulses));
+        }
+        for (sensor, visitor) in self.sensor_overlaps.drain() {
+            self.sensor_events.push(SensorEvent { kind: SensorEventKind::Exit, sensor, visitor });
+        }
+        self.contact_impulses.clear();
+    }
+
+    pub fn contains(&self, handle: BodyHandle) -> bool {
+        self.body_index(handle).is_some()
+    }
+
+    pub fn get(&self, handle: BodyHandle) -> Option<&RigidBody> {
+        self.body_index(handle).map(|index| &self.bodies[index])
+    }
+
+    /// Mutable access, wakes the body up so changes such as a new velocity take effect.
+    /// Static bodies never wake, what rests on them or hangs from them is woken instead.
+    pub fn get_mut(&mut self, handle: BodyHandle) -> Option<&mut RigidBody> {
+        let index = self.body_index(handle)?;
+        if self.bodies[index].is_static() {
+            self.wake_touching(handle);
+        }
+        let body = &mut self.bodies[index];
+        body.set_awake(true);
+        Some(body)
+    }
+
+    /// All bodies with their handles, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (BodyHandle, &RigidBody)> {
+        self.handles.iter().copied().zip(self.bodies.iter())
+    }
+
+    /// Mutable access to every body, wakes them all up like `get_mut`,
+    /// which also wakes whatever rests on the static ones
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (BodyHandle, &mut RigidBody)> {
+        for body in &mut self.bodies {
+            body.set_awake(true);
+        }
+        self.handles.iter().copied().zip(self.bodies.iter_mut())
+    }
+
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bodies.is_empty()
+    }
+
+    /// Contact manifolds found in the last step
+    pub fn contacts(&self) -> &[ContactManifold] {
+        &self.contacts
+    }
+
+    /// Takes the contact events of the steps since the last call, oldest first.
+    /// Events pile up until drained, so call this every frame once any are of interest.
+    pub fn drain_contact_events(&mut self) -> Vec<ContactEvent> {
+        std::mem::take(&mut self.contact_events)
+    }
+
+    /// Takes the sensor enter and exit events of the steps since the last call, oldest first.
+    /// Like contact events they pile up until drained.
+    pub fn drain_sensor_events(&mut self) -> Vec<SensorEvent> {
+        std::mem::take(&mut self.sensor_events)
+    }
+
+    /// Connects two bodies, the joint is solved together with the contacts
+    pub fn add_joint(&mut self, joint: impl Into<Joint>) -> JointHandle {
+        let joint: Joint = joint.into();
+        let (body_a, body_b) = joint.bodies();
+        self.wake_up(body_a);
+        self.wake_up(body_b);
+        let joint = Some(joint);
+        match self.free_joint_slots.pop() {
+            Some(index) => {
+                self.joints[index].joint = joint;
+                JointHandle::new(index, self.joints[index].generation)
+            }
+            None => {
+                self.joints.push(JointSlot { generation: 0, joint });
+                JointHandle::new(self.joints.len() - 1, 0)
+            }
+        }
+    }
+
+    pub fn remove_joint(&mut self, handle: JointHandle) -> Option<Joint> {
+        let slot = self.joints.get_mut(handle.index())
+            .filter(|slot| slot.generation == handle.generation())?;
+        let joint = slot.joint.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_joint_slots.push(handle.index());
+        let (body_a, body_b) = joint.bodies();
+        self.wake_up(body_a);
+        self.wake_up(body_b);
+        Some(joint)
+    }
+
+    pub fn get_joint(&self, handle: JointHandle) -> Option<&Joint> {
+        self.joints.get(handle.index())
+            .filter(|slot| slot.generation == handle.generation())?
+            .joint.as_ref()
+    }
+
+    /// Mutable access, wakes the joint's bodies up so changes such as a new motor speed take effect
+    pub fn get_joint_mut(&mut self, handle: JointHandle) -> Option<&mut Joint> {
+        let (body_a, body_b) = self.get_joint(handle)?.bodies();
+        self.wake_up(body_a);
+        self.wake_up(body_b);
+        self.joints[handle.index()].joint.as_mut()
+    }
+
+    pub fn joints(&self) -> impl Iterator<Item = (JointHandle, &Joint)> {
+        self.joints.iter().enumerate().filter_map(|(index, slot)| {
+            slot.joint.as_ref().map(|joint| (JointHandle::new(index, slot.generation), joint))
+        })
+    }
+
+    /// Adds a spring, its force is applied every step until it's removed
+    pub fn add_spring(&mut self, spring: Spring) -> SpringHandle {
+        self.wake_spring_bodies(spring.bodies());
+        let spring = Some(spring);
+        match self.free_spring_slots.pop() {
+            Some(index) => {
+                self.springs[index].spring = spring;
+                SpringHandle::new(index, self.springs[index].generation)
+            }
+            None => {
+                self.springs.push(SpringSlot { generation: 0, spring });
+                SpringHandle::new(self.springs.len() - 1, 0)
+            }
+        }
+    }
+
+    pub fn remove_spring(&mut self, handle: SpringHandle) -> Option<Spring> {
+        let slot = self.springs.get_mut(handle.index())
+            .filter(|slot| slot.generation == handle.generation())?;
+        let spring = slot.spring.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_spring_slots.push(handle.index());
+        self.wake_spring_bodies(spring.bodies());
+        Some(spring)
+    }
+
+    pub fn get_spring(&self, handle: SpringHandle) -> Option<&Spring> {
+        self.springs.get(handle.index())
+            .filter(|slot| slot.generation == handle.generation())?
+            .spring.as_ref()
+    }
+
+    /// Mutable access, e.g. to move the world point of a drag spring. Wakes the spring's bodies up.
+    pub fn get_spring_mut(&mut self, handle: SpringHandle) -> Option<&mut Spring> {
+        let bodies = self.get_spring(handle)?.bodies();
+        self.wake_spring_bodies(bodies);
+        self.springs[handle.index()].spring.as_mut()
+    }
+
+    pub fn springs(&self) -> impl Iterator<Item = (SpringHandle, &Spring)> {
+        self.springs.iter().enumerate().filter_map(|(index, slot)| {
+            slot.spring.as_ref().map(|spring| (SpringHandle::new(index, slot.generation), spring))
+        })
+    }
+
+    fn body_index(&self, handle: BodyHandle) -> Option<usize> {
+        slot_body(&self.slots, handle)
+    }
+
+    /// Wakes a body up, stale handles are ignored
+    fn wake_up(&mut self, handle: BodyHandle) {
+        if let Some(index) = self.body_index(handle) {
+            self.bodies[index].set_awake(true);
+        }
+    }
+
+    /// Wakes up the bodies in contact with the body or tied to it by a joint or spring
+    fn wake_touching(&mut self, handle: BodyHandle) {
+        let touching: Vec<BodyHandle> = self.contacts.iter()
+            .filter_map(|manifold| match (manifold.body_a == handle, manifold.body_b == handle) {
+                (true, _) => Some(manifold.body_b),
+                (_, true) => Some(manifold.body_a),
+                _ => None,
+            })
+            .chain(self.joints().flat_map(|(_, joint)| {
+                let (body_a, body_b) = joint.bodies();
+                if body_a == handle || body_b == handle { vec![body_a, body_b] } else { Vec::new() }
+            }))
+            .chain(self.springs().filter(|(_, spring)| spring.connects(handle)).flat_map(|(_, spring)| {
+                let (body_a, body_b) = spring.bodies();
+                std::iter::once(body_a).chain(body_b)
+            }))
+            .collect();
+        for other in touching {
+            self.wake_up(other);
+        }
+    }
+
+    /// Wakes up the bodies a spring is attached to
+    fn wake_spring_bodies(&mut self, (body_a, body_b): (BodyHandle, Option<BodyHandle>)) {
+        self.wake_up(body_a);
+        if let Some(body_b) = body_b {
+            self.wake_up(body_b);
+        }
     }
     
+    /// Advances the simulation by real elapsed time in steps of `fixed_dt`.
+    /// The remainder carries over to the next call, returns the number of steps taken.
+    /// Does nothing while `fixed_dt` isn't positive, or for a `real_dt` that's negative or not finite.
+    pub fn advance(&mut self, real_dt: f32) -> usize {
+        // Stepping by zero would never use up the time and `%` would make the accumulator NaN for good,
+        // so would adding a NaN or infinite time
+        let usable = self.fixed_dt > 0.0 && self.fixed_dt.is_finite() && real_dt >= 0.0 && real_dt.is_finite();
+        if !usable {
+            return 0;
+        }
+
+        self.accumulator += real_dt;
+
+        let mut steps = 0;
+        while self.accumulator >= self.fixed_dt && steps < self.max_substeps {
+            self.step(self.fixed_dt);
+            self.accumulator -= self.fixed_dt;
+            steps += 1;
+        }
+        if self.accumulator >= self.fixed_dt {
+            self.accumulator %= self.fixed_dt;
+        }
+
+        self.alpha = self.accumulator / self.fixed_dt;
+        steps
+    }
+
+    /// How far the leftover time of `advance` is into the next step (0..1),
+    /// blend factor between each body's previous and current transform for rendering.
+    /// 1 after a plain `step`.
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
     pub fn step(&mut self, dt: f32) {
+        // Remember where the bodies were, for interpolated rendering
+        for body in &mut self.bodies {
+            body.previous_position = body.position;
+            body.previous_angle = body.angle;
+        }
+        self.alpha = 1.0;
+
         // Clear forces
         for body in &mut self.bodies {
             body.force = Vector2D::zero();
             body.torque = 0.0;
+            if !self.allow_sleeping {
+                body.set_awake(true);
+            }
         }
         
         // Apply gravity
         for body in &mut self.bodies {
-            if !body.is_static {
+            if body.is_awake() {
                 body.apply_force(self.gravity * body.mass);
             }
         }
+
+        // Force generators
+        for spring in self.springs.iter().filter_map(|slot| slot.spring.as_ref()) {
+            let (handle_a, handle_b) = spring.bodies();
+            let Some(index_a) = slot_body(&self.slots, handle_a) else { continue };
+            let index_b = handle_b.and_then(|handle| slot_body(&self.slots, handle));
+            // Both ends sleep together, a spring at rest doesn't wake them
+            if !self.bodies[index_a].is_awake() && !index_b.is_some_and(|index| self.bodies[index].is_awake()) {
+                continue;
+            }
+            let (force, anchor_a, anchor_b) = spring.force(&self.bodies[index_a], index_b.map(|index| &self.bodies[index]));
+            self.bodies[index_a].apply_force_at_point(force, anchor_a);
+            if let Some(index_b) = index_b {
+                self.bodies[index_b].apply_force_at_point(force * -1.0, anchor_b);
+            }
+        }
         
-        // Detect collisions
+        // Broadphase, find the pairs whose bounding boxes overlap
+        self.update_broadphase();
+
+        // Pairs held by a joint that doesn't let its bodies collide
+        let jointed: HashSet<(BodyHandle, BodyHandle)> = self.joints()
+            .filter(|(_, joint)| !joint.constraint().collide_connected())
+            .map(|(_, joint)| {
+                let (body_a, body_b) = joint.bodies();
+                (body_a.min(body_b), body_a.max(body_b))
+            })
+            .collect();
+
+        // Detect collisions, sensor pairs only record that they overlap.
+        // Pairs with no awake body are skipped, their contacts from the last step are kept below.
         let mut contacts = Vec::new();
-        for i in 0..self.bodies.len() {
-            for j in (i + 1)..self.bodies.len() {
-                if let Some(contact) = detect_collision(&self.bodies[i], &self.bodies[j], i, j) {
-                    contacts.push(contact);
+        let mut sensor_overlaps = HashSet::new();
+        let (slots, bodies) = (&self.slots, &self.bodies);
+        let is_active = |slot: usize| slots[slot].body.is_some_and(|index| bodies[index].is_awake());
+        for (slot_a, slot_b) in self.broadphase.collect_active_pairs(&is_active) {
+            let (Some(i), Some(j)) = (self.slots[slot_a].body, self.slots[slot_b].body) else { continue };
+            if !self.pair_collides(i, j, &jointed) {
+                continue;
+            }
+            let (body_a, body_b) = (&self.bodies[i], &self.bodies[j]);
+            let (handle_a, handle_b) = (self.handles[i], self.handles[j]);
+            let Some(manifold) = detect_collision(body_a, body_b, handle_a, handle_b) else { continue };
+            if body_a.is_sensor || body_b.is_sensor {
+                if manifold.is_overlapping() {
+                    let pair = if body_a.is_sensor { (handle_a, handle_b) } else { (handle_b, handle_a) };
+                    sensor_overlaps.insert(pair);
                 }
+            } else {
+                contacts.push(manifold);
             }
         }
-        
-        // Resolve collisions
-        for contact in &contacts {
-            self.resolve_collision(contact);
+
+        // Contacts and sensor overlaps between sleeping (or static) bodies, carried over from the last step
+        let asleep = |handle: BodyHandle| slot_body(&self.slots, handle).is_some_and(|index| !self.bodies[index].is_awake());
+        let (resting, mut previous): (Vec<ContactManifold>, Vec<ContactManifold>) = std::mem::take(&mut self.contacts)
+            .into_iter()
+            .partition(|manifold| asleep(manifold.body_a) && asleep(manifold.body_b));
+        let resting_overlaps: Vec<(BodyHandle, BodyHandle)> = self.sensor_overlaps.iter()
+            .filter(|(sensor, visitor)| asleep(*sensor) && asleep(*visitor))
+            .copied()
+            .collect();
+
+        // Wake up every island an awake body is on, e.g. sleeping bodies hit by an awake one
+        let mut islands = self.build_islands(contacts.iter().chain(&resting));
+        let mut island_awake = vec![false; self.bodies.len()];
+        for index in 0..self.bodies.len() {
+            if self.bodies[index].is_awake() {
+                island_awake[islands.find(index)] = true;
+            }
+        }
+        for (kinematic, body) in islands.attached() {
+            if self.bodies[kinematic].is_awake() {
+                island_awake[islands.find(body)] = true;
+            }
+        }
+        for index in 0..self.bodies.len() {
+            if self.bodies[index].is_dynamic() && island_awake[islands.find(index)] {
+                self.bodies[index].set_awake(true);
+            }
+        }
+
+        // The kept contacts of the bodies just woken up are detected again and solved with the rest
+        let awake = |handle: BodyHandle| slot_body(&self.slots, handle).is_some_and(|index| self.bodies[index].is_awake());
+        let body_of = |handle: BodyHandle| &self.bodies[slot_body(&self.slots, handle).expect("contact with a live body")];
+        let (mut resting, woken): (Vec<ContactManifold>, Vec<ContactManifold>) = resting.into_iter()
+            .partition(|manifold| !awake(manifold.body_a) && !awake(manifold.body_b));
+        for manifold in &woken {
+            contacts.extend(detect_collision(body_of(manifold.body_a), body_of(manifold.body_b), manifold.body_a, manifold.body_b));
+        }
+        previous.extend(woken);
+        for (sensor, visitor) in resting_overlaps {
+            let still_overlapping = !awake(sensor) && !awake(visitor)
+                || detect_collision(body_of(sensor), body_of(visitor), sensor, visitor)
+                    .is_some_and(|manifold| manifold.is_overlapping());
+            if still_overlapping {
+                sensor_overlaps.insert((sensor, visitor));
+            }
         }
         
-        // Integrate physics
+        // Integrate velocities
+        let fields = &self.force_fields;
+        let linear_damping = self.damping;
+        let acceleration = |body: &RigidBody, position: Vector2D, velocity: Vector2D| {
+            fields.iter().fold(body.force * body.inv_mass, |sum, field| sum + field(position, velocity))
+        };
+        // RK4 integrates the unconstrained motion up front as (position, velocity) per body
+        let mut rk4_states = Vec::new();
         for body in &mut self.bodies {
-            if !body.is_static {
-                // Linear integration (Euler method)
-                body.acceleration = body.force * body.inv_mass;
-                body.velocity += body.acceleration * dt;
-                body.velocity *= self.damping;
-                body.position += body.velocity * dt;
-                
-                // Angular integration
-                body.angular_acceleration = body.torque * body.inv_moment_of_inertia;
-                body.angular_velocity += body.angular_acceleration * dt;
-                body.angular_velocity *= self.angular_damping;
-                body.angle += body.angular_velocity * dt;
+            // Kinematic bodies just move with the velocity they were given
+            if !body.is_awake() || body.is_kinematic() {
+                rk4_states.push((body.position + body.velocity * dt, body.velocity));
+                continue;
+            }
+
+            body.acceleration = acceleration(body, body.position, body.velocity);
+            match self.integrator {
+                Integrator::SemiImplicitEuler => {
+                    body.velocity += body.acceleration * dt;
+                    body.velocity *= damping_factor(linear_damping, dt);
+                }
+                Integrator::VelocityVerlet => {
+                    // First half kick, the second one follows the position update
+                    body.velocity += body.acceleration * (dt / 2.0);
+                    body.velocity *= damping_factor(linear_damping, dt / 2.0);
+                }
+                Integrator::Rk4 => {
+                    let state = rk4(body.position, body.velocity, dt, |position, velocity| {
+                        acceleration(body, position, velocity) - velocity * linear_damping
+                    });
+                    body.velocity = state.1;
+                    rk4_states.push(state);
+                }
             }
+
+            // Angular integration
+            body.angular_acceleration = body.torque * body.inv_moment_of_inertia;
+            body.angular_velocity += body.angular_acceleration * dt;
+            body.angular_velocity *= damping_factor(self.angular_damping, dt);
         }
-    }
-    
-    fn resolve_collision(&mut self, contact: &Contact) {
-        let body_a = &self.bodies[contact.body_a_index];
-        let body_b = &self.bodies[contact.body_b_index];
         
-        // Calculate relative velocity
-        let relative_velocity = body_b.velocity - body_a.velocity;
-        let velocity_along_normal = relative_velocity.dot(&contact.normal);
-        
-        // Don't resolve if velocities are separating
-        if velocity_along_normal > 0.0 {
-            return;
+        // Resolve collisions and joints, iterating so impulses can propagate through stacks and chains
+        let cache = if self.warm_starting { Some(&self.contact_impulses) } else { None };
+        let slots = &self.slots;
+        let index_of = |handle: BodyHandle| slot_body(slots, handle).expect("contact with a live body");
+        let mut solver = ContactSolver::new(&contacts, &self.bodies, index_of, cache, dt);
+        if self.warm_starting {
+            solver.warm_start(&mut self.bodies);
         }
-        
-        // Calculate restitution
-        let e = (body_a.restitution + body_b.restitution) / 2.0;
-        
-        // Calculate impulse scalar
-        let j = -(1.0 + e) * velocity_along_normal;
-        let j = j / (body_a.inv_mass + body_b.inv_mass);
-        
-        // Apply impulse
-        let impulse = contact.normal * j;
-        
-        // We need to borrow mutably, so we'll do this carefully
-        let (inv_mass_a, inv_mass_b) = (body_a.inv_mass, body_b.inv_mass);
-        
-        if contact.body_a_index < contact.body_b_index {
-            let (left, right) = self.bodies.split_at_mut(contact.body_b_index);
-            left[contact.body_a_index].apply_impulse(impulse * -1.0);
-            right[0].apply_impulse(impulse);
-        } else {
-            let (left, right) = self.bodies.split_at_mut(contact.body_a_index);
-            left[contact.body_b_index].apply_impulse(impulse);
-            right[0].apply_impulse(impulse * -1.0);
+
+        // Joints with the positions of their bodies in `bodies`, those of sleeping islands rest with them.
+        // A joint added with a stale handle has nothing to hold and is skipped like a spring would be.
+        let bodies = &self.bodies;
+        let mut joints: Vec<(usize, usize, &mut dyn JointConstraint)> = self.joints.iter_mut()
+            .filter_map(|slot| slot.joint.as_mut())
+            .filter_map(|joint| {
+                let (body_a, body_b) = joint.bodies();
+                let (index_a, index_b) = (slot_body(slots, body_a)?, slot_body(slots, body_b)?);
+                Some((index_a, index_b, joint.constraint_mut()))
+            })
+            .filter(|(index_a, index_b, _)| {
+                let (body_a, body_b) = (&bodies[*index_a], &bodies[*index_b]);
+                (body_a.is_awake() || body_b.is_awake()) && (body_a.is_dynamic() || body_b.is_dynamic())
+            })
+            .collect();
+        for (index_a, index_b, joint) in &mut joints {
+            joint.prepare(&self.bodies[*index_a], &self.bodies[*index_b], dt, self.warm_starting);
+            let mut state = VelocityState::load(&self.bodies, *index_a, *index_b);
+            joint.warm_start(&mut state);
+            state.store(&mut self.bodies, *index_a, *index_b);
+        }
+
+        for _ in 0..self.velocity_iterations {
+            for (index_a, index_b, joint) in &mut joints {
+                let mut state = VelocityState::load(&self.bodies, *index_a, *index_b);
+                joint.solve_velocity(&mut state, dt);
+                state.store(&mut self.bodies, *index_a, *index_b);
+            }
+            solver.solve_velocity_constraints(&mut self.bodies);
         }
+        self.contact_impulses = solver.impulses();
         
-        // Position correction to prevent sinking
-        let correction = contact.normal * (contact.penetration * 0.8);
-        let total_inv_mass = inv_mass_a + inv_mass_b;
+        // Integrate positions
+        for (index, body) in self.bodies.iter_mut().enumerate() {
+            if body.is_awake() {
+                match self.integrator {
+                    Integrator::SemiImplicitEuler | Integrator::VelocityVerlet => {
+                        body.position += body.velocity * dt;
+                    }
+                    Integrator::Rk4 => {
+                        // RK4 path plus the velocity change the contacts made
+                        let (position, velocity) = rk4_states[index];
+                        body.position = position + (body.velocity - velocity) * dt;
+                    }
+                }
+                body.angle += body.angular_velocity * dt;
+            }
+        }
         
-        if total_inv_mass > 0.0 {
-            let correction_a = correction * (inv_mass_a / total_inv_mass);
-            let correction_b = correction * (inv_mass_b / total_inv_mass);
-            
-            if contact.body_a_index < contact.body_b_index {
-                let (left, right) = self.bodies.split_at_mut(contact.body_b_index);
-                left[contact.body_a_index].position -= correction_a;
-                right[0].position += correction_b;
+        // Position correction to prevent sinking and joint drift
+        for _ in 0..self.position_iterations {
+            let mut joints_solved = true;
+            for (index_a, index_b, joint) in &joints {
+                let mut state = PositionState::load(&self.bodies, *index_a, *index_b);
+                joints_solved &= joint.solve_position(&mut state);
+                state.store(&mut self.bodies, *index_a, *index_b);
+            }
+            let contacts_solved = solver.solve_position_constraints(&mut self.bodies);
+            if contacts_solved && joints_solved {
+                break;
+            }
+        }
+
+        // Second half kick of velocity Verlet, with the acceleration at the new position
+        if self.integrator == Integrator::VelocityVerlet {
+            for body in &mut self.bodies {
+                if body.is_awake() && body.is_dynamic() {
+                    body.acceleration = acceleration(body, body.position, body.velocity);
+                    body.velocity += body.acceleration * (dt / 2.0);
+                    body.velocity *= damping_factor(linear_damping, dt / 2.0);
+                }
+            }
+        }
+
+        // Sweep bullets over the step so they stop at the first thing they hit instead of tunneling
+        let impacts = self.solve_bullets(dt, &jointed);
+
+        // Islands that came to rest fall asleep
+        self.update_sleep(dt, &mut islands);
+
+        // Contact events, pairs are matched against the last step's manifolds.
+        // Contacts of sleeping bodies are kept without reporting them until the bodies wake up.
+        let touched: HashSet<(BodyHandle, BodyHandle)> = previous.iter()
+            .map(|manifold| (manifold.body_a, manifold.body_b))
+            .collect();
+        for manifold in &contacts {
+            let kind = if touched.contains(&(manifold.body_a, manifold.body_b)) {
+                ContactEventKind::Persist
+            } else {
+                ContactEventKind::Begin
+            };
+            self.contact_events.push(ContactEvent::new(kind, manifold, &self.contact_impulses));
+        }
+        let mut still_touching: HashSet<(BodyHandle, BodyHandle)> = contacts.iter()
+            .map(|manifold| (manifold.body_a, manifold.body_b))
+            .collect();
+        // Bullet impacts count as contacts of this step, so a hit reports Begin and End once they part.
+        // Their impulses stay out of the warm start cache, a hit is no guess for the next step.
+        for (manifold, impulses) in impacts {
+            if !still_touching.insert((manifold.body_a, manifold.body_b)) {
+                continue;
+            }
+            let kind = if touched.contains(&(manifold.body_a, manifold.body_b)) {
+                ContactEventKind::Persist
+            } else {
+                ContactEventKind::Begin
+            };
+            self.contact_events.push(ContactEvent::new(kind, &manifold, &impulses));
+            contacts.push(manifold);
+        }
+        for manifold in &previous {
+            if !still_touching.contains(&(manifold.body_a, manifold.body_b)) {
+                self.contact_events.push(ContactEvent::new(ContactEventKind::End, manifold, &self.contact_impulses));
+            }
+        }
+
+        contacts.append(&mut resting);
+        self.contacts = contacts;
+
+        // Sensor events
+        for &(sensor, visitor) in &sensor_overlaps {
+            if !self.sensor_overlaps.contains(&(sensor, visitor)) {
+                self.sensor_events.push(SensorEvent { kind: SensorEventKind::Enter, sensor, visitor });
+            }
+        }
+        for &(sensor, visitor) in &self.sensor_overlaps {
+            if !sensor_overlaps.contains(&(sensor, visitor)) {
+                self.sensor_events.push(SensorEvent { kind: SensorEventKind::Exit, sensor, visitor });
+            }
+        }
+        self.sensor_overlaps = sensor_overlaps;
+
+        // Boxes at the final positions, for queries between steps
+        self.update_broadphase();
+    }
+
+    /// Islands of the dynamic bodies joined by `contacts`, joints and springs.
+    /// Static bodies don't join islands, a pile resting on the ground isn't tied to everything else on it,
+    /// kinematic bodies are attached to the islands they touch.
+    fn build_islands<'a>(&self, contacts: impl Iterator<Item = &'a ContactManifold>) -> Islands {
+        let mut islands = Islands::new(self.bodies.len());
+        let mut join = |handle_a: BodyHandle, handle_b: BodyHandle| {
+            let (Some(index_a), Some(index_b)) = (self.body_index(handle_a), self.body_index(handle_b)) else { return };
+            let (body_a, body_b) = (&self.bodies[index_a], &self.bodies[index_b]);
+            if body_a.is_dynamic() && body_b.is_dynamic() {
+                islands.join(index_a, index_b);
+            } else if body_a.is_kinematic() && body_b.is_dynamic() {
+                islands.attach(index_a, index_b);
+            } else if body_a.is_dynamic() && body_b.is_kinematic() {
+                islands.attach(index_b, index_a);
+            }
+        };
+        for manifold in contacts {
+            join(manifold.body_a, manifold.body_b);
+        }
+        for (_, joint) in self.joints() {
+            let (body_a, body_b) = joint.bodies();
+            join(body_a, body_b);
+        }
+        for (_, spring) in self.springs() {
+            if let (body_a, Some(body_b)) = spring.bodies() {
+                join(body_a, body_b);
+            }
+        }
+        islands
+    }
+
+    /// Advances the sleep timers of the awake bodies and puts every island whose bodies have all
+    /// been resting for `time_to_sleep` to sleep
+    fn update_sleep(&mut self, dt: f32, islands: &mut Islands) {
+        if !self.allow_sleeping {
+            return;
+        }
+
+        let linear_tolerance = self.linear_sleep_tolerance * self.linear_sleep_tolerance;
+        let angular_tolerance = self.angular_sleep_tolerance * self.angular_sleep_tolerance;
+        // Shortest rest of any body on each island, indexed by the island's representative
+        let mut island_rest = vec![f32::MAX; self.bodies.len()];
+        for (index, body) in self.bodies.iter_mut().enumerate() {
+            if !body.is_awake() {
+                continue;
+            }
+            // A kinematic body moving however slowly isn't resting, it would stop when put to sleep
+            let resting = if body.is_kinematic() {
+                body.can_sleep && body.velocity == Vector2D::zero() && body.angular_velocity == 0.0
             } else {
-                let (left, right) = self.bodies.split_at_mut(contact.body_a_index);
-                left[contact.body_b_index].position += correction_b;
-                right[0].position -= correction_a;
+                // Speed over the step, rather than the velocity: Verlet's second half kick and RK4's
+                // path leave a body resting on the ground with a small velocity it never moves by
+                let linear_speed = (body.position - body.previous_position) / dt;
+                let angular_speed = (body.angle - body.previous_angle) / dt;
+                body.can_sleep
+                    && linear_speed.magnitude_squared() <= linear_tolerance
+                    && angular_speed * angular_speed <= angular_tolerance
+            };
+            body.sleep_time = if resting { body.sleep_time + dt } else { 0.0 };
+            let island = islands.find(index);
+            island_rest[island] = island_rest[island].min(body.sleep_time);
+        }
+        for (kinematic, body) in islands.attached() {
+            if self.bodies[kinematic].is_awake() {
+                let island = islands.find(body);
+                island_rest[island] = island_rest[island].min(self.bodies[kinematic].sleep_time);
+            }
+        }
+
+        for (index, body) in self.bodies.iter_mut().enumerate() {
+            if body.is_awake() && island_rest[islands.find(index)] >= self.time_to_sleep {
+                body.set_awake(false);
+            }
+        }
+    }
+
+    /// Whether the bodies at `index_a` and `index_b` in `bodies` may collide: at least one is dynamic,
+    /// their filters accept each other, no joint between them keeps them apart and the pair filter agrees
+    fn pair_collides(&self, index_a: usize, index_b: usize, jointed: &HashSet<(BodyHandle, BodyHandle)>) -> bool {
+        let (body_a, body_b) = (&self.bodies[index_a], &self.bodies[index_b]);
+        let (handle_a, handle_b) = (self.handles[index_a], self.handles[index_b]);
+        (body_a.is_dynamic() || body_b.is_dynamic())
+            && body_a.filter.should_collide(&body_b.filter)
+            && !jointed.contains(&(handle_a.min(handle_b), handle_a.max(handle_b)))
+            && self.pair_filter.as_ref().is_none_or(|filter| filter(handle_a, body_a, handle_b, body_b))
+    }
+
+    /// Continuous collision for bullets. Each bullet is swept from where it started the step to where it ended,
+    /// against every other body swept the same way. At the first time of impact the bullet is moved there,
+    /// the contacts of that pair are solved, and the rest of the step is swept again from that point
+    /// with the new velocity, up to `MAX_BULLET_SUBSTEPS` times.
+    /// Returns the manifold and impulses of every impact, for contact events.
+    fn solve_bullets(&mut self, dt: f32, jointed: &HashSet<(BodyHandle, BodyHandle)>) -> Vec<(ContactManifold, ImpulseCache)> {
+        let mut impacts = Vec::new();
+        if !self.bodies.iter().any(|body| body.is_bullet) {
+            return impacts;
+        }
+        let sweeps: Vec<Sweep> = self.bodies.iter()
+            .map(|body| Sweep::new(body.previous_position, body.previous_angle, body.position, body.angle))
+            .collect();
+        // Proxies over everything the bodies covered this step, the end of `step` moves them back
+        for (index, sweep) in sweeps.iter().enumerate() {
+            let swept = swept_aabb(&self.bodies[index].shape, sweep);
+            self.broadphase.update(self.handles[index].index(), swept.expanded(CONTACT_MARGIN));
+        }
+
+        for bullet in 0..self.bodies.len() {
+            let body = &self.bodies[bullet];
+            if !body.is_bullet || !body.is_awake() || !body.is_dynamic() || body.is_sensor {
+                continue;
+            }
+
+            let core = Shape::circle(0.25 * inner_radius(&body.shape));
+            let mut sweep = sweeps[bullet];
+            let mut remaining = dt;
+            for substep in 0..MAX_BULLET_SUBSTEPS {
+                // After the first impact the others are done moving, sweep against where they ended up
+                let sweep_of = |other: usize| if substep == 0 {
+                    sweeps[other]
+                } else {
+                    Sweep::fixed(self.bodies[other].position, self.bodies[other].angle)
+                };
+                let shape = &self.bodies[bullet].shape;
+                let swept = swept_aabb(shape, &sweep);
+
+                // (other body, impact, whether only the core hit)
+                let mut first_hit: Option<(usize, TimeOfImpact, bool)> = None;
+                // Earliest fraction a sweep gave up at
+                let mut stalled: Option<f32> = None;
+                for slot in self.broadphase.query_aabb(&swept) {
+                    let Some(other) = self.slots[slot].body else { continue };
+                    let other_body = &self.bodies[other];
+                    if other == bullet || other_body.is_sensor || !self.pair_collides(bullet, other, jointed) {
+                        continue;
+                    }
+                    let other_sweep = sweep_of(other);
+                    if !swept_aabb(&other_body.shape, &other_sweep).overlaps(&swept) {
+                        continue;
+                    }
+                    let (impact, core_hit) = match sweep_impact(shape, &sweep, &other_body.shape, &other_sweep) {
+                        // Already touching at the start, which the regular contacts handle, but spinning or
+                        // sliding along it could still carry the bullet through. Sweep a small core around
+                        // its center instead, that only stops the bullet once its center is about to get in.
+                        // A core that already overlaps is past saving, leave it to the position correction.
+                        SweepImpact::Hit(hit) if hit.fraction == 0.0 => {
+                            let impact = match sweep_impact(&core, &sweep, &other_body.shape, &other_sweep) {
+                                SweepImpact::Hit(hit) if hit.fraction == 0.0 && !closing(&sweep, &other_sweep, hit.normal) => SweepImpact::Miss,
+                                impact => impact,
+                            };
+                            (impact, true)
+                        }
+                        impact => (impact, false),
+                    };
+                    match impact {
+                        SweepImpact::Hit(hit) => if first_hit.is_none_or(|(_, first, _)| hit.fraction < first.fraction) {
+                            first_hit = Some((other, hit, core_hit));
+                        },
+                        SweepImpact::Stalled(fraction) => stalled = Some(stalled.map_or(fraction, |first| first.min(fraction))),
+                        SweepImpact::Miss => {}
+                    }
+                }
+
+                // Nothing is hit before a sweep gave up, move there and sweep the rest of the step again
+                if let Some(fraction) = stalled
+                    && first_hit.is_none_or(|(_, hit, _)| fraction < hit.fraction)
+                {
+                    let (position, angle) = sweep.transform(fraction);
+                    let body = &mut self.bodies[bullet];
+                    body.position = position;
+                    body.angle = angle;
+                    remaining *= 1.0 - fraction;
+                    sweep = Sweep::new(position, angle,
+                                       position + body.velocity * remaining, angle + body.angular_velocity * remaining);
+                    continue;
+                }
+
+                let Some((other, hit, core_hit)) = first_hit else {
+                    let (position, angle) = sweep.transform(1.0);
+                    self.bodies[bullet].position = position;
+                    self.bodies[bullet].angle = angle;
+                    break;
+                };
+
+                let (position, angle) = sweep.transform(hit.fraction);
+                let body = &mut self.bodies[bullet];
+                body.position = position;
+                body.angle = angle;
+
+                // Solve the contacts of the pair at the impact, over what's left of the step
+                remaining *= 1.0 - hit.fraction;
+                let (bullet_handle, other_handle) = (self.handles[bullet], self.handles[other]);
+                // A core hit is solved at the core, so the center stops however fast the bullet spins
+                let mut probe = self.bodies[bullet].clone();
+                if core_hit {
+                    probe.shape = core.clone();
+                }
+                // Same order as the broadphase pairs, so the events match up with the regular contacts
+                let manifold = if bullet_handle.index() < other_handle.index() {
+                    detect_collision_within(&probe, &self.bodies[other], bullet_handle, other_handle, CONTACT_MARGIN)
+                } else {
+                    detect_collision_within(&self.bodies[other], &probe, other_handle, bullet_handle, CONTACT_MARGIN)
+                };
+                let Some(manifold) = manifold else { break };
+                // A sleeping body would keep the velocity it's given here without moving
+                self.bodies[other].set_awake(true);
+                let index_of = |handle: BodyHandle| if handle == bullet_handle { bullet } else { other };
+                let mut solver = ContactSolver::new(std::slice::from_ref(&manifold), &self.bodies, index_of, None, remaining.max(f32::EPSILON));
+                for _ in 0..self.velocity_iterations {
+                    solver.solve_velocity_constraints(&mut self.bodies);
+                }
+                impacts.push((manifold, solver.impulses()));
+
+                let body = &self.bodies[bullet];
+                sweep = Sweep::new(position, angle,
+                                   position + body.velocity * remaining, angle + body.angular_velocity * remaining);
             }
         }
+        impacts
     }
-}
\ No newline at end of file
+
+    /// Moves every body's proxy to its current box.
+    /// Boxes are grown by the contact margin so near contacts are still reported.
+    fn update_broadphase(&mut self) {
+        for (handle, body) in self.handles.iter().zip(&self.bodies) {
+            self.broadphase.update(handle.index(), body.aabb().expanded(CONTACT_MARGIN));
+        }
+    }
+}
+
+/// Box around everything a shape covers over its sweep. A rotating shape can swing out
+/// past its boxes at both ends, so it's bounded by the circle it spins in instead.
+fn swept_aabb(shape: &Shape, sweep: &Sweep) -> Aabb {
+    if sweep.start_angle == sweep.end_angle {
+        return shape.aabb(sweep.start_position, sweep.start_angle)
+            .union(&shape.aabb(sweep.end_position, sweep.end_angle));
+    }
+    let reach = Vector2D::new(1.0, 1.0) * bounding_radius(shape);
+    Aabb::new(sweep.start_position - reach, sweep.start_position + reach)
+        .union(&Aabb::new(sweep.end_position - reach, sweep.end_position + reach))
+}
+
+/// Whether the centers of two sweeps move towards each other along `normal` (from A to B),
+/// false for a zero normal
+fn closing(sweep_a: &Sweep, sweep_b: &Sweep, normal: Vector2D) -> bool {
+    let relative_motion = (sweep_b.end_position - sweep_b.start_position)
+        - (sweep_a.end_position - sweep_a.start_position);
+    relative_motion.dot(&normal) < 0.0
+}
+
+/// Position in `bodies` of a live body, `None` for a stale handle
+fn slot_body(slots: &[BodySlot], handle: BodyHandle) -> Option<usize> {
+    let slot = slots.get(handle.index())?;
+    if slot.generation == handle.generation() { slot.body } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn box_on_ground(integrator: Integrator) -> (World, BodyHandle) {
+        let mut world = World::new();
+        world.integrator = integrator;
+        world.add_body(RigidBody::new(Vector2D::new(400.0, 590.0), Shape::rectangle(800.0, 20.0), 0.0));
+        let body = world.add_body(RigidBody::new(Vector2D::new(400.0, 569.0), Shape::rectangle(20.0, 20.0), 1.0));
+        (world, body)
+    }
+
+    fn ball(x: f32) -> RigidBody {
+        RigidBody::new(Vector2D::new(x, 100.0), Shape::circle(10.0), 1.0)
+    }
+
+    #[test]
+    fn removed_handle_stays_stale_after_its_slot_is_reused() {
+        let mut world = World::new();
+        let removed = world.add_body(ball(100.0));
+        let kept = world.add_body(ball(200.0));
+
+        assert!(world.remove_body(removed).is_some());
+        let reused = world.add_body(ball(300.0));
+        assert_eq!(reused.index(), removed.index());
+        assert_ne!(reused, removed);
+
+        assert!(!world.contains(removed));
+        assert!(world.get(removed).is_none());
+        assert!(world.get_mut(removed).is_none());
+        assert!(world.remove_body(removed).is_none());
+        // The body that moved into the freed place and the new one still resolve to themselves
+        assert_eq!(world.get(kept).unwrap().position.x, 200.0);
+        assert_eq!(world.get(reused).unwrap().position.x, 300.0);
+        assert_eq!(world.len(), 2);
+
+        world.clear();
+        let after_clear = world.add_body(ball(400.0));
+        for handle in [kept, reused] {
+            assert!(world.get(handle).is_none());
+        }
+        assert_eq!(world.get(after_clear).unwrap().position.x, 400.0);
+    }
+
+    #[test]
+    fn advance_runs_whole_steps_and_carries_the_remainder() {
+        let mut world = World::new();
+        // Exact in binary, so the accumulator has no rounding to hide behind
+        world.fixed_dt = 0.25;
+        let body = world.add_body(ball(100.0));
+
+        assert_eq!(world.advance(0.625), 2);
+        assert_eq!(world.alpha(), 0.5);
+        let falling = world.get(body).unwrap();
+        assert!(falling.position.y > falling.previous_position.y);
+
+        // The leftover half step and this one make a whole step
+        assert_eq!(world.advance(0.125), 1);
+        assert_eq!(world.alpha(), 0.0);
+        assert_eq!(world.advance(0.125), 0);
+        assert_eq!(world.alpha(), 0.5);
+
+        world.step(0.25);
+        assert_eq!(world.alpha(), 1.0);
+    }
+
+    #[test]
+    fn advance_caps_substeps_and_drops_the_backlog() {
+        let mut world = World::new();
+        world.fixed_dt = 0.25;
+        world.max_substeps = 4;
+
+        assert_eq!(world.advance(10.125), 4);
+        assert_eq!(world.alpha(), 0.5);
+        // Only the fraction of a step is left, not the 36 steps that didn't fit
+        assert_eq!(world.advance(0.0), 0);
+    }
+
+    #[test]
+    fn advance_ignores_unusable_times() {
+        let mut world = World::new();
+        world.fixed_dt = 0.25;
+        assert_eq!(world.advance(0.125), 0);
+
+        for real_dt in [-1.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(world.advance(real_dt), 0);
+            assert_eq!(world.alpha(), 0.5, "{real_dt} changed the leftover time");
+        }
+        assert_eq!(world.advance(0.125), 1);
+
+        world.fixed_dt = 0.0;
+        assert_eq!(world.advance(1.0), 0);
+        world.fixed_dt = 0.25;
+        assert_eq!(world.advance(0.25), 1);
+    }
+
+    #[test]
+    fn resting_body_falls_asleep_with_every_integrator() {
+        for integrator in [Integrator::SemiImplicitEuler, Integrator::VelocityVerlet, Integrator::Rk4] {
+            let (mut world, body) = box_on_ground(integrator);
+            for _ in 0..120 {
+                world.step(1.0 / 60.0);
+            }
+            assert!(!world.get(body).unwrap().is_awake(), "{integrator:?} box still awake");
+        }
+    }
+}